@@ -4,6 +4,7 @@ var filesToCache = [
   './index.html',
   './lost_ark_refiner.js',
   './lost_ark_refiner_bg.wasm',
+  './worker.js',
 ];
 
 /* Start the service worker and cache all of the app's content */
@@ -0,0 +1,19 @@
+// Runs the solver off the page's thread. The page starts this worker and
+// talks to it through messages; see `src/app/wasm_worker.rs`.
+importScripts("./lost_ark_refiner.js");
+
+// Hold on to anything the page sends before the wasm is ready.
+var pending = [];
+self.onmessage = function (event) {
+    pending.push(event);
+};
+
+wasm_bindgen("./lost_ark_refiner_bg.wasm")
+    .then(function () {
+        // This replaces `self.onmessage` with the real handler.
+        wasm_bindgen.worker_main();
+        pending.forEach(function (event) {
+            self.onmessage(event);
+        });
+        pending = [];
+    })["catch"](console.error);
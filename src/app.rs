@@ -1,8 +1,7 @@
 use eframe::{egui, epi};
 
-mod chance;
-mod solution;
 mod widgets;
+mod worker;
 
 #[cfg(not(target_arch = "wasm32"))]
 mod worker_thread;
@@ -11,40 +10,45 @@ mod worker_thread;
 #[path = "app/wasm_worker.rs"]
 mod worker_thread;
 
-use self::solution::Scoring;
-use self::widgets::{GameState, Simulation, Weights};
-use self::worker_thread::ThreadHandle;
+use self::widgets::{PresetLibrary, Profiles, RngAudit, ScoreKind, Simulation};
+use self::worker::ThreadHandle;
+#[cfg(target_arch = "wasm32")]
+pub(crate) use self::worker_thread::worker_main;
+use crate::solver::{Risk, Scoring, SharedStone, SuccessRates};
+
+/// Where the web build is hosted; shared links point here.
+const SHARE_URL: &str = "https://jgallagher.github.io/lostark-refining/";
 
-#[derive(Debug, Clone, Copy)]
-struct SimResult {
-    counts: [u8; 3],
-    probability: f64,
-    score: f64,
+/// How to find the most likely final outcomes.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+enum SimMethod {
+    /// Compute the exact probability of every outcome.
+    Exact,
+    /// Estimate probabilities from `tries` simulated runs, drawn from a
+    /// ChaCha8 generator seeded with `seed`.
+    Sampled { tries: u32, seed: u64 },
+    /// Simulate until every result shown has a 95% confidence interval
+    /// narrower than `width`, or `seconds` run out.
+    Adaptive { width: f64, seconds: u32, seed: u64 },
 }
 
 #[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
-#[derive(PartialEq)]
+#[derive(PartialEq, Default)]
 enum LightOrDarkMode {
+    #[default]
     Light,
     Dark,
 }
 
-impl Default for LightOrDarkMode {
-    fn default() -> Self {
-        Self::Light
-    }
-}
-
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
 #[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "persistence", serde(default))] // if we add new fields, give them default values when deserializing old state
 #[derive(Default)]
 pub struct TemplateApp {
-    weights: Weights,
-    selected_preset: usize,
+    profiles: Profiles,
+    presets: PresetLibrary,
     simulation: Simulation,
-    sim_tries: Option<u32>,
-    game_state: GameState,
+    audit: RngAudit,
     light_or_dark: LightOrDarkMode,
 
     // this how you opt-out of serialization of a member
@@ -52,7 +56,47 @@ pub struct TemplateApp {
     current_scoring: Option<Scoring>,
 
     #[cfg_attr(feature = "persistence", serde(skip))]
-    worker_thread: Option<worker_thread::ThreadHandle>,
+    current_risk: Option<Risk>,
+
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    current_rates: Option<SuccessRates>,
+
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    sim_method: Option<SimMethod>,
+
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    worker_thread: Option<ThreadHandle>,
+
+    // Link or code pasted into the "Open" box, and why it didn't open.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    share_text: String,
+
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    share_error: Option<String>,
+
+    // Weights that came with an opened stone, until the user says whether
+    // to use them.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    shared_scoring: Option<Scoring>,
+}
+
+/// Takes the fragment off the page's address without reloading it.
+#[cfg(target_arch = "wasm32")]
+fn clear_location_hash() {
+    let window = match web_sys::window() {
+        Some(window) => window,
+        None => return,
+    };
+    let location = window.location();
+    let url = format!(
+        "{}{}",
+        location.pathname().unwrap_or_default(),
+        location.search().unwrap_or_default()
+    );
+    if let Ok(history) = window.history() {
+        let _ =
+            history.replace_state_with_url(&eframe::wasm_bindgen::JsValue::NULL, "", Some(&url));
+    }
 }
 
 fn set_light_mode(ctx: &egui::CtxRef) {
@@ -93,11 +137,33 @@ impl epi::App for TemplateApp {
         );
         ctx.set_fonts(fonts);
 
+        // A link to a shared stone opens it on top of whatever we last had
+        // open. The code comes out of the address so reloading doesn't open
+        // it again.
+        if let Some(web_info) = &frame.info().web_info {
+            let code = web_info.web_location_hash.trim_start_matches('#');
+            if let Ok(shared) = code.parse::<SharedStone>() {
+                self.shared_scoring = self.profiles.current_mut().open_shared(shared);
+                #[cfg(target_arch = "wasm32")]
+                clear_location_hash();
+            }
+        }
+
         // spawn worker thread
+        let profile = self.profiles.current();
+        let risk = profile.weights.risk();
+        self.current_risk = Some(risk);
+        let rates = self.audit.rates(&self.profiles.entered_clicks());
+        self.current_rates = Some(rates.clone());
+        let sim_method = self.simulation.method();
+        self.sim_method = Some(sim_method);
         let worker_thread = ThreadHandle::spawn(
-            self.weights.parse(),
-            self.game_state.clone(),
-            self.sim_tries,
+            profile.weights.parse(),
+            risk,
+            rates,
+            profile.stone().game_state.clone(),
+            Some(sim_method),
+            self.simulation.compares_policies(),
             frame.repaint_signal(),
         );
         self.worker_thread = Some(worker_thread);
@@ -114,14 +180,19 @@ impl epi::App for TemplateApp {
     /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
     fn update(&mut self, ctx: &egui::CtxRef, _frame: &mut epi::Frame<'_>) {
         let Self {
-            weights,
-            selected_preset,
+            profiles,
+            presets,
             simulation,
-            sim_tries,
-            game_state,
+            sim_method,
+            audit,
             light_or_dark,
             current_scoring,
+            current_risk,
+            current_rates,
             worker_thread,
+            share_text,
+            share_error,
+            shared_scoring,
         } = self;
 
         match light_or_dark {
@@ -129,7 +200,8 @@ impl epi::App for TemplateApp {
             LightOrDarkMode::Dark => set_dark_mode(ctx),
         }
 
-        let worker_thread = worker_thread.as_ref().unwrap();
+        let worker_thread = worker_thread.as_mut().unwrap();
+        worker_thread.supervise();
 
         // Examples of how to create different panels and windows.
         // Pick whichever suits you.
@@ -148,6 +220,62 @@ impl epi::App for TemplateApp {
 
                 ui.selectable_value(light_or_dark, LightOrDarkMode::Light, "Light Mode");
                 ui.selectable_value(light_or_dark, LightOrDarkMode::Dark, "Dark Mode");
+
+                ui.separator();
+                if profiles.show_picker(ui, audit) {
+                    // The weights on offer were for the profile we just left.
+                    *shared_scoring = None;
+                    worker_thread.update_game_state(profiles.current().stone().game_state.clone());
+                }
+
+                ui.separator();
+                if ui
+                    .add_enabled(current_scoring.is_some(), egui::Button::new("Copy Link"))
+                    .on_hover_text("Copy a link to this stone and these weights")
+                    .clicked()
+                {
+                    let shared = SharedStone {
+                        game_state: profiles.current().stone().game_state.clone(),
+                        scoring: current_scoring.unwrap(),
+                    };
+                    ui.output().copied_text = format!("{}#{}", SHARE_URL, shared);
+                }
+                ui.add(
+                    egui::TextEdit::singleline(share_text)
+                        .hint_text("Paste a link")
+                        .desired_width(200.0),
+                );
+                if ui.button("Open").clicked() {
+                    let code = share_text.rsplit('#').next().unwrap_or_default();
+                    match code.parse::<SharedStone>() {
+                        Ok(shared) => {
+                            let profile = profiles.current_mut();
+                            *shared_scoring = profile.open_shared(shared);
+                            worker_thread.update_game_state(profile.stone().game_state.clone());
+                            share_text.clear();
+                            *share_error = None;
+                        }
+                        Err(err) => *share_error = Some(err),
+                    }
+                }
+                if let Some(err) = share_error {
+                    ui.colored_label(egui::Color32::RED, err.as_str());
+                }
+                if let Some(scoring) = *shared_scoring {
+                    ui.separator();
+                    ui.label("The stone came with different weights.");
+                    if ui
+                        .button("Use them")
+                        .on_hover_text("Replace this profile's weights with the ones in the link")
+                        .clicked()
+                    {
+                        profiles.current_mut().weights.set_scoring(scoring);
+                        *shared_scoring = None;
+                    }
+                    if ui.button("Keep mine").clicked() {
+                        *shared_scoring = None;
+                    }
+                }
             });
         });
 
@@ -156,10 +284,31 @@ impl epi::App for TemplateApp {
 
             ui.vertical(|ui| {
                 ui.group(|ui| {
-                    let prev_state = game_state.clone();
-                    game_state.show(ui, worker_thread.sorted_choices(&prev_state));
-                    if prev_state != *game_state {
-                        worker_thread.update_game_state(game_state.clone());
+                    let profile = profiles.current_mut();
+                    if profile.show_stone_tabs(ui, audit) {
+                        worker_thread.update_game_state(profile.stone().game_state.clone());
+                    }
+
+                    let stone = profile.stone_mut();
+                    let prev_state = stone.game_state.clone();
+                    let score_kind = if current_scoring
+                        .as_ref()
+                        .is_some_and(Scoring::is_probability)
+                    {
+                        ScoreKind::Probability
+                    } else if *current_risk != Some(Risk::Neutral) {
+                        ScoreKind::RiskAdjusted
+                    } else {
+                        ScoreKind::Average
+                    };
+                    stone.game_state.show(
+                        ui,
+                        worker_thread.sorted_choices(),
+                        score_kind,
+                    );
+                    if prev_state != stone.game_state {
+                        stone.track(&prev_state, audit);
+                        worker_thread.update_game_state(stone.game_state.clone());
                     }
                 });
 
@@ -172,25 +321,52 @@ impl epi::App for TemplateApp {
                     |ui| {
                         egui::Grid::new("weights-help").show(ui, |ui| {
                             ui.group(|ui| {
-                                let scoring = weights.show(ui, selected_preset);
-                                if let Some(scoring) = scoring {
-                                    // Update our & worker thread's scoring
-                                    if Some(scoring) != *current_scoring {
-                                        *current_scoring = Some(scoring);
-                                        worker_thread.update_weights(scoring);
+                                ui.vertical(|ui| {
+                                    let profile = profiles.current_mut();
+                                    let scoring = profile.weights.show(
+                                        ui,
+                                        &mut profile.selected_preset,
+                                        presets.presets(),
+                                    );
+                                    if let Some(scoring) = scoring {
+                                        // Update our & worker thread's scoring
+                                        if Some(scoring) != *current_scoring {
+                                            *current_scoring = Some(scoring);
+                                            worker_thread.update_weights(scoring);
+                                        }
                                     }
-                                }
+                                    let risk = profile.weights.risk();
+                                    if Some(risk) != *current_risk {
+                                        *current_risk = Some(risk);
+                                        worker_thread.update_risk(risk);
+                                    }
+                                    if let Some(scoring) = *current_scoring {
+                                        profile
+                                            .weights
+                                            .show_size_warning(ui, scoring, &profile.stone().game_state);
+                                    }
+                                    presets.show(ui, *current_scoring);
+                                });
                             });
                             ui.end_row();
 
                             ui.group(|ui| {
                                 ui.vertical(|ui| {
                                     ui.heading("Help / About");
-                                    ui.label("\u{2022} Set point values for success/failure for each row in `Weights` above");
+                                    ui.label("\u{2022} Set point values for success/failure for each row in `Weights` above, or a goal to reach");
                                     ui.label("\u{2022} Reported scores are the expected values based on current progress and chosen weights");
+                                    ui.label("\u{2022} In goal mode, reported scores are the probability of reaching the goal");
+                                    ui.label("\u{2022} Pick a risk setting to trade some average score for more consistent results");
                                     ui.label("\u{2022} Follow the suggested selections (green highlit skill)");
                                     ui.label("\u{2022} Update the top section with the in-game result (+1 or failure)");
-                                    ui.label("\u{2022} The right section shows the 10 most probable final outcomes");
+                                    ui.label("\u{2022} Undo/Redo step through your clicks in the order you made them");
+                                    ui.label("\u{2022} The right section shows every possible final outcome, sortable by any column; Skill 1, Skill 2 and Negative break it down into how likely each row is to end with exactly and at least each number of successes");
+                                    ui.label("\u{2022} By choice shows what each click from here leads to, as final scores or as successes in one row");
+                                    ui.label("\u{2022} Strategies under the outcomes compares the solver with rules of thumb like clicking skills at 45% and up");
+                                    ui.label("\u{2022} RNG Audit compares the clicks you've entered with the advertised chances");
+                                    ui.label("\u{2022} Save your own presets under Saved presets, and copy or import them to share with others");
+                                    ui.label("\u{2022} Profiles keep separate weights and stones; use the tabs above the stone to switch between stones");
+                                    ui.label("\u{2022} Copy Link shares this stone and these weights; paste a link and click Open to load it as a new stone");
                                     ui.horizontal(|ui| {
                                         ui.spacing_mut().item_spacing.x = 0.0;
                                         ui.label("\u{2022} Problems/suggestions/question? Open an ");
@@ -201,12 +377,29 @@ impl epi::App for TemplateApp {
                             ui.end_row();
                         });
 
-                        ui.group(|ui| {
-                            let tries = simulation.show(ui, worker_thread.sim_results());
-                            if Some(tries) != *sim_tries {
-                                *sim_tries = Some(tries);
-                                worker_thread.update_sim_tries(tries);
-                            }
+                        ui.vertical(|ui| {
+                            ui.group(|ui| {
+                                let method = simulation.show(
+                                    ui,
+                                    worker_thread.sim_results(),
+                                    worker_thread.choice_outcomes(),
+                                    worker_thread.policy_outcomes(),
+                                    profiles.current().weights.goal(),
+                                );
+                                if Some(method) != *sim_method {
+                                    *sim_method = Some(method);
+                                    worker_thread.update_sim_method(method);
+                                }
+                                worker_thread.update_compare_policies(simulation.compares_policies());
+                            });
+
+                            ui.group(|ui| {
+                                let rates = audit.show(ui, &profiles.entered_clicks());
+                                if Some(&rates) != current_rates.as_ref() {
+                                    worker_thread.update_rates(rates.clone());
+                                    *current_rates = Some(rates);
+                                }
+                            });
                         });
                     },
                 );
@@ -214,7 +407,37 @@ impl epi::App for TemplateApp {
         });
 
         egui::TopBottomPanel::bottom("bottom-panel").show(ctx, |ui| {
-            ui.label(worker_thread.status());
+            ui.horizontal(|ui| {
+                let status = worker_thread.status();
+                if let Some(crash) = worker_thread.crash() {
+                    let action = if crash.restarted {
+                        "restarted it"
+                    } else {
+                        "change something to try again"
+                    };
+                    ui.colored_label(
+                        egui::Color32::RED,
+                        format!("The solver crashed ({}); {}", crash.message, action),
+                    );
+                } else if status.is_error() {
+                    ui.colored_label(egui::Color32::RED, status.to_string());
+                } else {
+                    ui.label(status.to_string());
+                }
+                if let Some(progress) = worker_thread.progress().filter(|_| status.is_busy()) {
+                    ui.add(
+                        egui::ProgressBar::new(progress)
+                            .desired_width(200.0)
+                            .show_percentage(),
+                    );
+                }
+                // A Web Worker only hears new requests between slices of an
+                // adaptive simulation; anything else runs to the end first.
+                #[cfg(target_arch = "wasm32")]
+                if status.is_busy() {
+                    ui.small("In the browser, changes wait for this step to finish");
+                }
+            });
 
             ui.with_layout(egui::Layout::bottom_up(egui::Align::RIGHT), |ui| {
                 ui.horizontal(|ui| {
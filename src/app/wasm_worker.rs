@@ -1,121 +1,150 @@
-use super::{
-    solution::{Answer, Scoring, Solution},
-    widgets::GameState,
-    SimResult,
-};
-use arrayvec::ArrayVec;
+//! The web build can't spawn threads, so the [`Engine`] runs in a Web Worker
+//! that loads this same module (see `docs/worker.js`). Requests and
+//! responses cross over as bytes, packed by [`encode`].
+
+use super::worker::{decode, encode, Engine, Request, Response, Results, Tagged, Transport};
 use eframe::epi::RepaintSignal;
-use std::{cell::RefCell, sync::Arc};
+use eframe::wasm_bindgen::{prelude::*, JsCast};
+use js_sys::Uint8Array;
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    sync::Arc,
+};
+use web_sys::{DedicatedWorkerGlobalScope, ErrorEvent, MessageEvent, Worker};
+
+// Loads this module in a worker and calls `worker_main`.
+const WORKER_SCRIPT: &str = "./worker.js";
 
-pub(super) struct ThreadHandle {
-    inner: RefCell<Inner>,
+/// Runs an [`Engine`] in a Web Worker.
+pub(super) struct Client {
+    worker: Worker,
+    results: Rc<RefCell<Results>>,
+    // Kept so the worker's responses and errors have somewhere to go.
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_error: Closure<dyn FnMut(ErrorEvent)>,
 }
 
-impl ThreadHandle {
-    pub(super) fn spawn(
-        scoring: Option<Scoring>,
-        game_state: GameState,
-        sim_tries: Option<u32>,
-        _repaint_signal: Arc<dyn RepaintSignal>,
-    ) -> Self {
-        let mut inner = Inner {
-            solution: None,
-            most_likely: None,
-            scoring,
-            sim_tries,
-            game_state,
-        };
-        inner.rebuild_solution();
-        Self {
-            inner: RefCell::new(inner),
-        }
-    }
+impl Client {
+    pub(super) fn spawn(repaint_signal: Arc<dyn RepaintSignal>) -> Self {
+        let worker = Worker::new(WORKER_SCRIPT).expect("couldn't start the solver worker");
+        let results = Rc::new(RefCell::new(Results::default()));
 
-    pub(super) fn status(&self) -> String {
-        let inner = self.inner.borrow();
-        match inner.solution.as_ref() {
-            Some(solution) => {
-                if inner.most_likely.is_some() {
-                    format!("Solved ({} states)", solution.num_states())
-                } else {
-                    format!(
-                        "Solved ({} states); running simulations...",
-                        solution.num_states()
-                    )
+        let on_message = {
+            let results = Rc::clone(&results);
+            let repaint_signal = Arc::clone(&repaint_signal);
+            Closure::wrap(Box::new(move |event: MessageEvent| {
+                let message = Uint8Array::new(&event.data()).to_vec();
+                if let Some(response) = decode(&message) {
+                    results.borrow_mut().receive(response);
+                    repaint_signal.request_repaint();
                 }
-            }
-            None => "Finding solution...".to_string(),
-        }
-    }
-
-    pub(super) fn update_weights(&self, scoring: Scoring) {
-        let mut inner = self.inner.borrow_mut();
-        inner.scoring = Some(scoring);
-        inner.rebuild_solution();
-    }
+            }) as Box<dyn FnMut(MessageEvent)>)
+        };
+        worker.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
 
-    pub(super) fn update_sim_tries(&self, sim_tries: u32) {
-        let mut inner = self.inner.borrow_mut();
-        inner.sim_tries = Some(sim_tries);
-        inner.rerun_simulation();
-    }
+        // A panic in the worker aborts its module, so there's no coming back
+        // from one.
+        let on_error = {
+            let results = Rc::clone(&results);
+            Closure::wrap(Box::new(move |event: ErrorEvent| {
+                results.borrow_mut().crashed(event.message());
+                repaint_signal.request_repaint();
+            }) as Box<dyn FnMut(ErrorEvent)>)
+        };
+        worker.set_onerror(Some(on_error.as_ref().unchecked_ref()));
 
-    pub(super) fn update_game_state(&self, game_state: GameState) {
-        let mut inner = self.inner.borrow_mut();
-        let prev_num_slots = inner.game_state.num_slots();
-        inner.game_state = game_state;
-        if prev_num_slots != inner.game_state.num_slots() {
-            inner.rebuild_solution();
-        } else {
-            inner.rerun_simulation();
+        Self {
+            worker,
+            results,
+            _on_message: on_message,
+            _on_error: on_error,
         }
     }
 
-    pub(super) fn sim_results(&self) -> Option<Vec<SimResult>> {
-        self.inner.borrow().most_likely.clone()
+    pub(super) fn send(&self, request: Tagged<Request>) {
+        self.worker
+            .post_message(&Uint8Array::from(&encode(&request)[..]))
+            .expect("couldn't message the solver worker");
     }
 
-    pub(super) fn sorted_choices(&self, state: &GameState) -> Option<ArrayVec<Answer, 3>> {
-        self.inner
-            .borrow()
-            .solution
-            .as_ref()
-            .and_then(|solution| solution.sorted_choices(state))
+    pub(super) fn with_results<T>(&self, f: impl FnOnce(&mut Results) -> T) -> T {
+        f(&mut self.results.borrow_mut())
     }
 }
 
-struct Inner {
-    solution: Option<Solution>,
-    most_likely: Option<Vec<SimResult>>,
-    scoring: Option<Scoring>,
-    sim_tries: Option<u32>,
-    game_state: GameState,
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.worker.terminate();
+    }
 }
 
-impl Inner {
-    fn rebuild_solution(&mut self) {
-        let scoring = match self.scoring {
-            Some(scoring) => scoring,
-            None => return,
-        };
+/// The worker's end of a `Client`. Requests arrive as events, so there's
+/// never one to wait for.
+struct WorkerScope(DedicatedWorkerGlobalScope);
 
-        self.solution = Some(Solution::build(scoring, self.game_state.num_slots()));
-        self.rerun_simulation();
+impl Transport for WorkerScope {
+    fn recv(&mut self) -> Option<Tagged<Request>> {
+        None
     }
 
-    fn rerun_simulation(&mut self) {
-        let sim_tries = match self.sim_tries {
-            Some(n) => n,
-            None => return,
-        };
+    fn try_recv(&mut self) -> Option<Tagged<Request>> {
+        None
+    }
 
-        let solution = match self.solution.as_ref() {
-            Some(s) => s,
-            None => return,
-        };
+    fn send(&mut self, response: Response) {
+        // The page can't go away without taking us with it.
+        let _ = self
+            .0
+            .post_message(&Uint8Array::from(&encode(&response)[..]));
+    }
+}
 
-        let most_likely = solution.simulate_top_10(sim_tries, &self.game_state);
+/// Entry point for the worker; serves requests from a `Client`.
+pub(crate) fn worker_main() {
+    let scope: DedicatedWorkerGlobalScope = js_sys::global().unchecked_into();
+    let engine = Rc::new(RefCell::new(Engine::new(false)));
+    let scheduled = Rc::new(Cell::new(false));
 
-        self.most_likely = Some(most_likely);
-    }
+    // Requests that arrive together are all applied before doing any work,
+    // so only the newest ones get solved. Work stopped at the end of a slice
+    // is scheduled the same way, so it carries on after any new requests.
+    // Filled in below, once there's a way to schedule it.
+    let catch_up: Rc<RefCell<Option<Closure<dyn FnMut()>>>> = Rc::default();
+    let schedule = {
+        let scope = scope.clone();
+        let scheduled = Rc::clone(&scheduled);
+        let catch_up = Rc::clone(&catch_up);
+        Rc::new(move || {
+            if !scheduled.replace(true) {
+                scope
+                    .set_timeout_with_callback_and_timeout_and_arguments_0(
+                        catch_up.borrow().as_ref().unwrap().as_ref().unchecked_ref(),
+                        0,
+                    )
+                    .expect("couldn't schedule solver work");
+            }
+        })
+    };
+    *catch_up.borrow_mut() = {
+        let engine = Rc::clone(&engine);
+        let schedule = Rc::clone(&schedule);
+        let mut transport = WorkerScope(scope.clone());
+        Some(Closure::wrap(Box::new(move || {
+            scheduled.set(false);
+            if engine.borrow_mut().catch_up(&mut transport) {
+                schedule();
+            }
+        }) as Box<dyn FnMut()>))
+    };
+    let on_message = Closure::wrap(Box::new(move |event: MessageEvent| {
+        let message = Uint8Array::new(&event.data()).to_vec();
+        if let Some(request) = decode(&message) {
+            engine.borrow_mut().apply(request);
+        }
+        schedule();
+    }) as Box<dyn FnMut(MessageEvent)>);
+    scope.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    // The worker lives as long as the page.
+    on_message.forget();
 }
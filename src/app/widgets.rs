@@ -1,7 +1,13 @@
+mod audit;
 mod game_state;
+mod presets;
+mod profiles;
 mod simulation;
 mod weights;
 
-pub(super) use game_state::GameState;
+pub(super) use audit::RngAudit;
+pub(super) use game_state::{ScoreKind, ALL_NUM_SLOTS};
+pub(super) use presets::PresetLibrary;
+pub(super) use profiles::Profiles;
 pub(super) use simulation::Simulation;
 pub(super) use weights::Weights;
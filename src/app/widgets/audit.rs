@@ -0,0 +1,99 @@
+use crate::solver::{goodness_of_fit, Chance, SuccessRates};
+use eframe::egui::{self, Ui};
+
+// A chance needs at least this many logged clicks before its observed rate
+// replaces the advertised one.
+const MIN_CLICKS_FOR_RATE: u32 = 30;
+
+// p-values below this are flagged as suspicious.
+const SIGNIFICANCE: f64 = 0.05;
+
+/// A log of every click entered by hand this session, used to check the
+/// game's advertised success rates.
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "persistence", serde(default))] // if we add new fields, give them default values when deserializing old state
+#[derive(Default)]
+pub(in crate::app) struct RngAudit {
+    // (chance shown, success) for every click on stones that have since
+    // been reset or closed.
+    archived: Vec<(Chance, bool)>,
+    use_observed: bool,
+}
+
+impl RngAudit {
+    /// Keeps clicks from a stone that's being closed or reset.
+    pub(in crate::app) fn archive(&mut self, clicks: impl IntoIterator<Item = (Chance, bool)>) {
+        self.archived.extend(clicks);
+    }
+
+    /// Every click logged so far, given the clicks on stones still open.
+    fn logged(&self, open: &[(Chance, bool)]) -> Vec<(Chance, bool)> {
+        self.archived.iter().chain(open).copied().collect()
+    }
+
+    /// The success rates the solver should use, given the clicks on stones
+    /// still open.
+    pub(in crate::app) fn rates(&self, open: &[(Chance, bool)]) -> SuccessRates {
+        if self.use_observed {
+            let fits = goodness_of_fit(self.logged(open));
+            SuccessRates::observed(&fits, MIN_CLICKS_FOR_RATE)
+        } else {
+            SuccessRates::advertised()
+        }
+    }
+
+    pub(in crate::app) fn show(&mut self, ui: &mut Ui, open: &[(Chance, bool)]) -> SuccessRates {
+        let logged = self.logged(open);
+        let fits = goodness_of_fit(logged.iter().copied());
+
+        egui::CollapsingHeader::new("RNG Audit").show(ui, |ui| {
+            ui.label(format!("{} clicks logged", logged.len()));
+            if !fits.is_empty() {
+                egui::Grid::new("rng-audit-grid").show(ui, |ui| {
+                    ui.label("Chance");
+                    ui.label("Clicks");
+                    ui.label("Successes");
+                    ui.label("Observed");
+                    ui.label("p-value");
+                    ui.end_row();
+
+                    for fit in &fits {
+                        ui.label(fit.chance.to_string());
+                        ui.label(fit.clicks.to_string());
+                        ui.label(fit.successes.to_string());
+                        ui.label(format!("{:.1}%", 100.0 * fit.observed_rate()));
+                        let p_value = format!("{:.3}", fit.p_value);
+                        if fit.p_value < SIGNIFICANCE {
+                            ui.colored_label(egui::Color32::RED, p_value);
+                        } else {
+                            ui.label(p_value);
+                        }
+                        ui.end_row();
+                    }
+                });
+                ui.label(format!(
+                    "A p-value under {} means a rate that far off would be unusual if the \
+                     advertised chance were right.",
+                    SIGNIFICANCE
+                ));
+            }
+
+            ui.checkbox(
+                &mut self.use_observed,
+                format!(
+                    "Solve with observed rates (chances with {}+ clicks)",
+                    MIN_CLICKS_FOR_RATE
+                ),
+            );
+            if ui
+                .button("Clear log")
+                .on_hover_text("Forget clicks from stones that are no longer open")
+                .clicked()
+            {
+                self.archived.clear();
+            }
+        });
+
+        self.rates(open)
+    }
+}
@@ -1,53 +1,10 @@
 use arrayvec::ArrayVec;
 use eframe::egui::{self, epaint, Ui, Vec2};
 
-use crate::app::{chance::Chance, solution::Answer};
+use crate::app::worker::Published;
+use crate::solver::{Answer, ChanceLadder, GameState};
 
-type Row = ArrayVec<bool, { ALL_NUM_SLOTS[ALL_NUM_SLOTS.len() - 1].0 as usize }>;
-
-#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
-#[cfg_attr(feature = "persistence", serde(default))] // if we add new fields, give them default values when deserializing old state
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub(in crate::app) struct GameState {
-    chance: Chance,
-    num_slots: u8,
-    rows: [Row; 3],
-}
-
-impl Default for GameState {
-    fn default() -> Self {
-        Self {
-            chance: Chance::SeventyFive,
-            num_slots: 8,
-            rows: [Row::new(), Row::new(), Row::new()],
-        }
-    }
-}
-
-impl GameState {
-    pub(in crate::app) fn chance(&self) -> Chance {
-        self.chance
-    }
-
-    pub(in crate::app) fn num_slots(&self) -> u8 {
-        self.num_slots
-    }
-
-    pub(in crate::app) fn row(&self, i: usize) -> &[bool] {
-        &self.rows[i]
-    }
-}
-
-const ALL_CHANCES: [Chance; 6] = [
-    Chance::SeventyFive,
-    Chance::SixtyFive,
-    Chance::FiftyFive,
-    Chance::FourtyFive,
-    Chance::ThirtyFive,
-    Chance::TwentyFive,
-];
-
-const ALL_NUM_SLOTS: [(u8, &str); 15] = [
+pub(in crate::app) const ALL_NUM_SLOTS: [(u8, &str); 15] = [
     (2, "2"),
     (3, "3"),
     (4, "4"),
@@ -95,61 +52,146 @@ const HIGHLIGHT_FRAME: egui::Frame = egui::Frame {
     },
 };
 
+// Marks a recommendation that's out of date.
+const STALE_FRAME: egui::Frame = egui::Frame {
+    margin: Vec2::new(2.0, 2.0),
+    corner_radius: 0.0,
+    shadow: epaint::Shadow {
+        extrusion: 0.0,
+        color: egui::Color32::TRANSPARENT,
+    },
+    fill: egui::Color32::TRANSPARENT,
+    stroke: egui::Stroke {
+        width: 2.0,
+        color: egui::Color32::GRAY,
+    },
+};
+
+/// What the scores passed to `GameState::show` measure.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(in crate::app) enum ScoreKind {
+    Average,
+    Probability,
+    RiskAdjusted,
+}
+
 impl GameState {
-    pub(in crate::app) fn show(&mut self, ui: &mut Ui, choices: Option<ArrayVec<Answer, 3>>) {
+    pub(in crate::app) fn show(
+        &mut self,
+        ui: &mut Ui,
+        choices: Option<Published<ArrayVec<Answer, 3>>>,
+        score_kind: ScoreKind,
+    ) {
         ui.vertical(|ui| {
             ui.heading("Lost Ark Ability Stone Refinement Optimizer");
             ui.horizontal(|ui| {
                 ui.label("Success Chance:");
+                let mut chance = self.chance();
                 egui::ComboBox::from_id_source("success-chance-combo")
-                    .selected_text(self.chance.as_str())
+                    .selected_text(chance.to_string())
                     .show_ui(ui, |ui| {
-                        for c in ALL_CHANCES {
-                            let text = c.as_str();
-                            ui.selectable_value(&mut self.chance, c, text);
+                        for c in self.ladder().levels().into_iter().rev() {
+                            ui.selectable_value(&mut chance, c, c.to_string());
                         }
                     });
+                self.set_chance(chance);
 
                 ui.label("Total Slots:");
+                let mut num_slots = self.num_slots();
                 egui::ComboBox::from_id_source("total-slots-combo")
-                    .selected_text(format!("{}", self.num_slots))
+                    .selected_text(format!("{}", num_slots))
                     .show_ui(ui, |ui| {
                         for (n, text) in ALL_NUM_SLOTS {
-                            ui.selectable_value(&mut self.num_slots, n, text);
+                            ui.selectable_value(&mut num_slots, n, text);
                         }
                     });
+                self.set_num_slots(num_slots);
 
                 if ui.button("RESET").clicked() {
-                    self.chance = Chance::SeventyFive;
-                    for r in &mut self.rows {
-                        r.clear();
-                    }
+                    self.reset();
+                }
+                if ui
+                    .add_enabled(self.can_undo(), egui::Button::new("Undo"))
+                    .clicked()
+                {
+                    self.undo();
+                }
+                if ui
+                    .add_enabled(self.can_redo(), egui::Button::new("Redo"))
+                    .clicked()
+                {
+                    self.redo();
                 }
             });
 
-            let num_slots = usize::from(self.num_slots);
-            for row in &mut self.rows {
-                row.truncate(num_slots);
+            self.show_rules(ui);
+
+            if !self.chance_is_reachable() {
+                ui.horizontal(|ui| {
+                    ui.colored_label(
+                        egui::Color32::RED,
+                        format!(
+                            "\u{26a0} A {} chance can't come up after these results.",
+                            self.chance()
+                        ),
+                    );
+                    let replayed = self.replayed_chance();
+                    if ui.button(format!("Use {}", replayed)).clicked() {
+                        self.set_chance(replayed);
+                    }
+                });
             }
 
             egui::Grid::new("main-state-grid")
                 .min_row_height(45.0)
                 .show(ui, |ui| {
-                    for (i, (&label, row)) in ROW_LABELS.iter().zip(&mut self.rows).enumerate() {
-                        show_slots_row(ui, label, num_slots, row, &mut self.chance, i, &choices)
+                    for i in 0..3 {
+                        show_slots_row(ui, self, i, &choices)
                     }
                 });
 
-            if let Some(mut choices) = choices {
+            if let Some(Published {
+                value: mut choices,
+                current,
+            }) = choices
+            {
                 ui.separator();
-                ui.label("Average Final Score for Each Choice");
+                ui.horizontal(|ui| {
+                    ui.label(match score_kind {
+                        ScoreKind::Average => "Average Final Score for Each Choice",
+                        ScoreKind::Probability => {
+                            "Probability of Reaching the Goal for Each Choice"
+                        }
+                        ScoreKind::RiskAdjusted => "Risk-Adjusted Final Score for Each Choice",
+                    });
+                    if !current {
+                        ui.colored_label(egui::Color32::GRAY, "(out of date; updating...)");
+                    }
+                });
                 let best = choices[0].index;
                 choices.sort_unstable_by_key(|a| a.index);
 
+                ui.set_enabled(current);
                 egui::Grid::new("each-choice-final-score-grid").show(ui, |ui| {
                     for choice in choices {
                         ui.label(ROW_LABELS[choice.index]);
-                        ui.label(format!("{:.3}", choice.score));
+                        match score_kind {
+                            ScoreKind::Average => {
+                                ui.label(format!("{:.3}", choice.score));
+                            }
+                            ScoreKind::Probability => {
+                                ui.label(format!("{:.2}%", 100.0 * choice.score));
+                            }
+                            ScoreKind::RiskAdjusted => {
+                                ui.label(format!("{:.3}", choice.score));
+                                if let Some(std_dev) = choice.std_dev {
+                                    ui.label(format!(
+                                        "(average {:.3} \u{b1} {:.3})",
+                                        choice.mean, std_dev
+                                    ));
+                                }
+                            }
+                        }
                         if choice.index == best {
                             ui.label("*** BEST ***");
                         }
@@ -161,37 +203,94 @@ impl GameState {
     }
 }
 
+impl GameState {
+    fn show_rules(&mut self, ui: &mut Ui) {
+        let mut ladder = *self.ladder();
+        egui::CollapsingHeader::new("Success Chance Rules").show(ui, |ui| {
+            egui::Grid::new("chance-rules-grid").show(ui, |ui| {
+                ui.label("Lowest chance");
+                ui.add(
+                    egui::DragValue::new(&mut ladder.min)
+                        .clamp_range(0..=100)
+                        .suffix("%"),
+                );
+                ui.end_row();
+
+                ui.label("Highest chance");
+                ui.add(
+                    egui::DragValue::new(&mut ladder.max)
+                        .clamp_range(ladder.min..=100)
+                        .suffix("%"),
+                );
+                ui.end_row();
+
+                ui.label("Starting chance");
+                ui.add(
+                    egui::DragValue::new(&mut ladder.start)
+                        .clamp_range(ladder.min..=ladder.max)
+                        .suffix("%"),
+                );
+                ui.end_row();
+
+                ui.label("Change after success");
+                ui.add(
+                    egui::DragValue::new(&mut ladder.on_success)
+                        .clamp_range(-100..=100)
+                        .suffix("%"),
+                );
+                ui.end_row();
+
+                ui.label("Change after failure");
+                ui.add(
+                    egui::DragValue::new(&mut ladder.on_fail)
+                        .clamp_range(-100..=100)
+                        .suffix("%"),
+                );
+                ui.end_row();
+            });
+
+            if ui.button("Restore defaults").clicked() {
+                ladder = ChanceLadder::default();
+            }
+        });
+
+        self.set_ladder(ladder);
+    }
+}
+
 fn show_slots_row(
     ui: &mut Ui,
-    label: &'static str,
-    num_slots: usize,
-    row: &mut Row,
-    chance: &mut Chance,
+    game_state: &mut GameState,
     row_index: usize,
-    optimal: &Option<ArrayVec<Answer, 3>>,
+    optimal: &Option<Published<ArrayVec<Answer, 3>>>,
 ) {
-    let label_frame = if optimal.as_ref().map(|a| a[0].index) == Some(row_index) {
-        &HIGHLIGHT_FRAME
-    } else {
-        &TRANSPARENT_FRAME
+    let label = ROW_LABELS[row_index];
+    let label_frame = match optimal {
+        Some(optimal) if optimal.value[0].index == row_index => {
+            if optimal.current {
+                &HIGHLIGHT_FRAME
+            } else {
+                &STALE_FRAME
+            }
+        }
+        _ => &TRANSPARENT_FRAME,
     };
     label_frame.show(ui, |ui| {
         ui.label(label);
     });
 
-    for i in 0..num_slots {
-        if let Some(&succeeded) = row.get(i) {
+    let row_len = game_state.row(row_index).len();
+    for i in 0..usize::from(game_state.num_slots()) {
+        if let Some(&succeeded) = game_state.row(row_index).get(i) {
             ui.label(if succeeded { "+1" } else { "fail" });
-        } else if i == row.len() {
+        } else if i == row_len {
             ui.vertical(|ui| {
                 let mut selected = -1;
                 if ui.radio_value(&mut selected, 1, "+1").clicked() {
-                    row.push(true);
-                    chance.down();
+                    game_state.record(row_index, true);
                 }
                 if ui.radio_value(&mut selected, 0, "fail").clicked() {
-                    row.push(false);
-                    chance.up();
+                    game_state.record(row_index, false);
                 }
             });
         } else {
@@ -199,16 +298,10 @@ fn show_slots_row(
         }
     }
     if ui
-        .add_enabled(!row.is_empty(), egui::Button::new("X"))
+        .add_enabled(row_len > 0, egui::Button::new("X"))
         .clicked()
     {
-        if let Some(prev_success) = row.pop() {
-            if prev_success {
-                chance.up();
-            } else {
-                chance.down();
-            }
-        }
+        game_state.remove_last_click(row_index);
     }
     ui.end_row();
 }
@@ -0,0 +1,234 @@
+use crate::solver::{read_presets, write_presets, Preset, Scoring};
+use eframe::egui::{self, Ui};
+use std::borrow::Cow;
+
+/// Presets saved by the user, on top of the built-in `PRESETS`.
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "persistence", serde(default))] // if we add new fields, give them default values when deserializing old state
+pub(in crate::app) struct PresetLibrary {
+    presets: Vec<Preset>,
+    // Where "Save file" and "Load file" read and write a preset pack.
+    #[cfg(not(target_arch = "wasm32"))]
+    path: String,
+
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    new_name: String,
+
+    // The preset being renamed and its name as typed so far, which only
+    // takes once it's valid and the user moves on.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    renaming: Option<(usize, String)>,
+
+    // Preset pack pasted into the import box.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    pack_text: String,
+
+    // Result of the last import or export, and whether it went wrong.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    message: Option<(String, bool)>,
+}
+
+impl Default for PresetLibrary {
+    fn default() -> Self {
+        Self {
+            presets: Vec::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            path: "presets.txt".to_string(),
+            new_name: String::new(),
+            renaming: None,
+            pack_text: String::new(),
+            message: None,
+        }
+    }
+}
+
+impl PresetLibrary {
+    pub(in crate::app) fn presets(&self) -> &[Preset] {
+        &self.presets
+    }
+
+    /// Adds `preset`, replacing any saved preset with the same name.
+    fn save(&mut self, preset: Preset) {
+        match self.presets.iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => existing.scoring = preset.scoring,
+            None => self.presets.push(preset),
+        }
+    }
+
+    /// Why preset `index` can't be called `name`, if it can't.
+    fn rename_error(&self, index: usize, name: &str) -> Option<String> {
+        let name = name.trim();
+        if name.is_empty() {
+            Some("Presets need a name".to_string())
+        } else if self
+            .presets
+            .iter()
+            .enumerate()
+            .any(|(i, preset)| i != index && preset.name == name)
+        {
+            Some(format!("There's already a preset called {}", name))
+        } else {
+            None
+        }
+    }
+
+    /// Shows the name of preset `index` for editing.
+    fn show_name(&mut self, ui: &mut Ui, index: usize) {
+        let id = ("saved-preset-name", index);
+        match &mut self.renaming {
+            Some((i, draft)) if *i == index => {
+                let mut draft = std::mem::take(draft);
+                let error = self.rename_error(index, &draft);
+                let mut edit = egui::TextEdit::singleline(&mut draft)
+                    .id_source(id)
+                    .desired_width(200.0);
+                if error.is_some() {
+                    edit = edit.text_color(egui::Color32::RED);
+                }
+                let response = ui.add(edit);
+                if response.lost_focus() {
+                    match error {
+                        Some(error) => self.message = Some((error, true)),
+                        None => self.presets[index].name = Cow::Owned(draft.trim().to_string()),
+                    }
+                    self.renaming = None;
+                } else {
+                    self.renaming = Some((index, draft));
+                }
+            }
+            _ => {
+                let mut name = self.presets[index].name.to_string();
+                let response = ui
+                    .add(
+                        egui::TextEdit::singleline(&mut name)
+                            .id_source(id)
+                            .desired_width(200.0),
+                    )
+                    .on_hover_text("Rename this preset");
+                if response.changed() {
+                    self.renaming = Some((index, name));
+                }
+            }
+        }
+    }
+
+    fn import(&mut self, text: &str) {
+        self.message = Some(match read_presets(text) {
+            Ok(presets) => {
+                let count = presets.len();
+                for preset in presets {
+                    self.save(preset);
+                }
+                (format!("Imported {} presets", count), false)
+            }
+            Err(err) => (err, true),
+        });
+    }
+
+    /// Shows controls to save `current` as a preset, edit and delete saved
+    /// presets, and import and export them.
+    pub(in crate::app) fn show(&mut self, ui: &mut Ui, current: Option<Scoring>) {
+        egui::CollapsingHeader::new("Saved presets").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.new_name)
+                        .hint_text("Preset name")
+                        .desired_width(200.0),
+                );
+                let name = self.new_name.trim();
+                if ui
+                    .add_enabled(
+                        current.is_some() && !name.is_empty(),
+                        egui::Button::new("Save"),
+                    )
+                    .on_hover_text("Save the current weights; replaces a preset with the same name")
+                    .clicked()
+                {
+                    self.save(Preset {
+                        name: Cow::Owned(name.to_string()),
+                        scoring: current.unwrap(),
+                    });
+                    self.new_name.clear();
+                }
+            });
+
+            let mut delete = None;
+            egui::Grid::new("saved-presets-grid").show(ui, |ui| {
+                for i in 0..self.presets.len() {
+                    self.show_name(ui, i);
+                    if ui
+                        .add_enabled(current.is_some(), egui::Button::new("Update"))
+                        .on_hover_text("Replace this preset with the current weights")
+                        .clicked()
+                    {
+                        self.presets[i].scoring = current.unwrap();
+                    }
+                    if ui.button("Delete").clicked() {
+                        delete = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+            if let Some(i) = delete {
+                self.presets.remove(i);
+                self.renaming = None;
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(!self.presets.is_empty(), egui::Button::new("Copy pack"))
+                    .on_hover_text("Copy every saved preset as text to share")
+                    .clicked()
+                {
+                    ui.output().copied_text = write_presets(&self.presets);
+                    self.message = Some(("Copied presets".to_string(), false));
+                }
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.pack_text)
+                        .hint_text("Paste a preset pack")
+                        .desired_width(200.0),
+                );
+                if ui.button("Import").clicked() {
+                    let text = std::mem::take(&mut self.pack_text);
+                    self.import(&text);
+                }
+            });
+
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.path).desired_width(200.0));
+                if ui
+                    .add_enabled(!self.presets.is_empty(), egui::Button::new("Save file"))
+                    .clicked()
+                {
+                    self.message = Some(
+                        match std::fs::write(&self.path, write_presets(&self.presets)) {
+                            Ok(()) => (format!("Saved presets to {}", self.path), false),
+                            Err(err) => (format!("Couldn't save {}: {}", self.path, err), true),
+                        },
+                    );
+                }
+                if ui.button("Load file").clicked() {
+                    match std::fs::read_to_string(&self.path) {
+                        Ok(text) => self.import(&text),
+                        Err(err) => {
+                            self.message =
+                                Some((format!("Couldn't read {}: {}", self.path, err), true))
+                        }
+                    }
+                }
+            });
+
+            match &self.message {
+                Some((message, true)) => {
+                    ui.colored_label(egui::Color32::RED, message.as_str());
+                }
+                Some((message, false)) => {
+                    ui.label(message.as_str());
+                }
+                None => {}
+            }
+        });
+    }
+}
@@ -0,0 +1,206 @@
+use super::{RngAudit, Weights};
+use crate::solver::{Chance, GameState, Scoring, SharedStone};
+use eframe::egui::{self, Ui};
+
+/// A stone being worked on.
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "persistence", serde(default))] // if we add new fields, give them default values when deserializing old state
+#[derive(Default)]
+pub(in crate::app) struct Stone {
+    pub(in crate::app) game_state: GameState,
+    // Number of clicks at the start of `game_state` that weren't entered by
+    // hand, e.g. because the stone was opened from a link.
+    imported: usize,
+}
+
+impl Stone {
+    /// Clicks on this stone that were entered by hand, with the chance each
+    /// was made at.
+    pub(in crate::app) fn entered_clicks(&self) -> impl Iterator<Item = (Chance, bool)> {
+        self.game_state.clicks().into_iter().skip(self.imported)
+    }
+
+    /// Keeps track of which clicks were entered by hand after `game_state`
+    /// changed from `old`. Clicks from a stone that was reset are handed to
+    /// `audit` so they aren't lost.
+    pub(in crate::app) fn track(&mut self, old: &GameState, audit: &mut RngAudit) {
+        let new = &self.game_state;
+        // Undoing every move also empties the history, but leaves the moves
+        // available to redo.
+        if new.history().is_empty() && !new.can_redo() && !old.history().is_empty() {
+            audit.archive(old.clicks().into_iter().skip(self.imported));
+            self.imported = 0;
+        }
+        self.imported = self.imported.min(new.clicks().len());
+    }
+}
+
+/// A player's weights and the stones they're working on.
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "persistence", serde(default))] // if we add new fields, give them default values when deserializing old state
+pub(in crate::app) struct Profile {
+    name: String,
+    pub(in crate::app) weights: Weights,
+    pub(in crate::app) selected_preset: usize,
+    stones: Vec<Stone>,
+    current_stone: usize,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::new("Main".to_string())
+    }
+}
+
+impl Profile {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            weights: Weights::default(),
+            selected_preset: 0,
+            stones: vec![Stone::default()],
+            current_stone: 0,
+        }
+    }
+
+    pub(in crate::app) fn stone(&self) -> &Stone {
+        &self.stones[self.current_stone]
+    }
+
+    pub(in crate::app) fn stone_mut(&mut self) -> &mut Stone {
+        &mut self.stones[self.current_stone]
+    }
+
+    /// Opens a shared stone next to the others and switches to it, or just
+    /// switches to it if it's already open. Returns the shared scoring if
+    /// it's different from this profile's weights, which are left alone.
+    pub(in crate::app) fn open_shared(&mut self, shared: SharedStone) -> Option<Scoring> {
+        let open = self.stones.iter().position(|stone| {
+            stone.game_state.same_rules(&shared.game_state)
+                && stone.game_state.history() == shared.game_state.history()
+        });
+        self.current_stone = match open {
+            Some(i) => i,
+            None => {
+                self.stones.push(Stone {
+                    imported: shared.game_state.clicks().len(),
+                    game_state: shared.game_state,
+                });
+                self.stones.len() - 1
+            }
+        };
+        Some(shared.scoring).filter(|&scoring| self.weights.parse() != Some(scoring))
+    }
+
+    /// Shows a tab for each stone. Returns true if a different stone is now
+    /// current.
+    pub(in crate::app) fn show_stone_tabs(&mut self, ui: &mut Ui, audit: &mut RngAudit) -> bool {
+        let before = self.current_stone;
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            for i in 0..self.stones.len() {
+                ui.selectable_value(&mut self.current_stone, i, format!("Stone {}", i + 1));
+            }
+            if ui
+                .button("+")
+                .on_hover_text("Start another stone")
+                .clicked()
+            {
+                let num_slots = self.stone().game_state.num_slots();
+                let ladder = *self.stone().game_state.ladder();
+                self.stones.push(Stone {
+                    game_state: GameState::new(num_slots, ladder),
+                    imported: 0,
+                });
+                self.current_stone = self.stones.len() - 1;
+            }
+            if ui
+                .add_enabled(self.stones.len() > 1, egui::Button::new("Close"))
+                .on_hover_text("Close this stone")
+                .clicked()
+            {
+                let stone = self.stones.remove(self.current_stone);
+                audit.archive(stone.entered_clicks());
+                self.current_stone = self.current_stone.min(self.stones.len() - 1);
+                // The index may not have changed, but the stone has.
+                changed = true;
+            }
+        });
+        changed || self.current_stone != before
+    }
+}
+
+/// Every profile, and which one is being worked on.
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "persistence", serde(default))] // if we add new fields, give them default values when deserializing old state
+pub(in crate::app) struct Profiles {
+    list: Vec<Profile>,
+    current: usize,
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Self {
+            list: vec![Profile::default()],
+            current: 0,
+        }
+    }
+}
+
+impl Profiles {
+    pub(in crate::app) fn current(&self) -> &Profile {
+        &self.list[self.current]
+    }
+
+    pub(in crate::app) fn current_mut(&mut self) -> &mut Profile {
+        &mut self.list[self.current]
+    }
+
+    /// Clicks entered by hand on every open stone in every profile.
+    pub(in crate::app) fn entered_clicks(&self) -> Vec<(Chance, bool)> {
+        self.list
+            .iter()
+            .flat_map(|p| &p.stones)
+            .flat_map(Stone::entered_clicks)
+            .collect()
+    }
+
+    /// Shows the profile picker for the menu bar. Returns true if a different
+    /// profile is now current.
+    pub(in crate::app) fn show_picker(&mut self, ui: &mut Ui, audit: &mut RngAudit) -> bool {
+        let before = self.current;
+        let mut changed = false;
+
+        ui.label("Profile:");
+        egui::ComboBox::from_id_source("profile-combo")
+            .selected_text(&self.current().name)
+            .show_ui(ui, |ui| {
+                for (i, profile) in self.list.iter().enumerate() {
+                    ui.selectable_value(&mut self.current, i, &profile.name);
+                }
+            });
+        ui.add(egui::TextEdit::singleline(&mut self.current_mut().name).desired_width(100.0))
+            .on_hover_text("Rename this profile");
+
+        if ui.button("New").on_hover_text("Add a profile").clicked() {
+            let name = format!("Profile {}", self.list.len() + 1);
+            self.list.push(Profile::new(name));
+            self.current = self.list.len() - 1;
+        }
+        if ui
+            .add_enabled(self.list.len() > 1, egui::Button::new("Delete"))
+            .on_hover_text("Delete this profile and its stones")
+            .clicked()
+        {
+            let profile = self.list.remove(self.current);
+            for stone in &profile.stones {
+                audit.archive(stone.entered_clicks());
+            }
+            self.current = self.current.min(self.list.len() - 1);
+            // The index may not have changed, but the profile has.
+            changed = true;
+        }
+
+        changed || self.current != before
+    }
+}
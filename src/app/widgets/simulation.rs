@@ -1,59 +1,532 @@
-use crate::app::SimResult;
+use crate::app::worker::{ChoiceOutcomes, PolicyOutcomes, Published};
+use crate::app::SimMethod;
+use crate::solver::{Goal, Policy, SimResult};
 use eframe::egui::{self, Ui};
+use std::hash::Hash;
+
+const ROW_LABELS: [&str; 3] = ["Skill 1", "Skill 2", "Negative"];
+
+// Width of a bar showing 100%.
+const BAR_WIDTH: f32 = 120.0;
 
 const PRESET_TRIES: [u32; 4] = [100, 1_000, 10_000, 100_000];
 
+// Widths of confidence interval an adaptive simulation can aim for.
+const PRESET_WIDTHS: [f64; 5] = [0.05, 0.02, 0.01, 0.005, 0.002];
+
+// Time limits for an adaptive simulation, in seconds.
+const PRESET_SECONDS: [u32; 4] = [1, 5, 15, 60];
+
+/// How to break down the outcomes.
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum View {
+    /// Every final result.
+    Outcomes,
+    /// How many successes one row ends up with.
+    Row(usize),
+    /// Final scores after each choice from the current state.
+    Choices,
+    /// How the solver does next to rules of thumb.
+    Policies,
+}
+
+/// A column the outcome table can be sorted by.
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Column {
+    Successes(usize),
+    Probability,
+    Score,
+}
+
 #[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "persistence", serde(default))] // if we add new fields, give them default values when deserializing old state
 pub(in crate::app) struct Simulation {
+    exact: bool,
     index: usize,
+    // Simulate until the results are precise enough, instead of a fixed
+    // number of runs.
+    adaptive: bool,
+    width_index: usize,
+    seconds_index: usize,
+    seed: u64,
+    view: View,
+    sort_by: Column,
+    ascending: bool,
+    // Which of the policies to show every final score for.
+    policy_index: usize,
+    // What "By choice" compares: how many successes a row ends with, or
+    // final scores if `None`.
+    choice_row: Option<usize>,
+
+    // The seed as typed, which may not parse yet.
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    seed_text: Option<String>,
 }
 
 impl Default for Simulation {
     fn default() -> Self {
         Self {
+            exact: true,
             index: PRESET_TRIES.iter().position(|&x| x == 10_000).unwrap_or(0),
+            adaptive: false,
+            width_index: PRESET_WIDTHS.iter().position(|&x| x == 0.01).unwrap_or(0),
+            seconds_index: PRESET_SECONDS.iter().position(|&x| x == 5).unwrap_or(0),
+            seed: new_seed(),
+            view: View::Outcomes,
+            sort_by: Column::Probability,
+            ascending: false,
+            policy_index: 0,
+            choice_row: None,
+            seed_text: None,
         }
     }
 }
 
+// Short enough to read out to someone.
+fn new_seed() -> u64 {
+    rand::random::<u32>().into()
+}
+
 impl Simulation {
-    pub(in crate::app) fn show(&mut self, ui: &mut Ui, most_likely: Option<Vec<SimResult>>) -> u32 {
+    pub(in crate::app) fn method(&self) -> SimMethod {
+        if self.exact {
+            SimMethod::Exact
+        } else if self.adaptive {
+            SimMethod::Adaptive {
+                width: PRESET_WIDTHS[self.width_index],
+                seconds: PRESET_SECONDS[self.seconds_index],
+                seed: self.seed,
+            }
+        } else {
+            SimMethod::Sampled {
+                tries: PRESET_TRIES[self.index],
+                seed: self.seed,
+            }
+        }
+    }
+
+    /// Whether the policy comparison is on screen, and so worth working out.
+    pub(in crate::app) fn compares_policies(&self) -> bool {
+        self.view == View::Policies
+    }
+
+    fn show_seed(&mut self, ui: &mut Ui) {
+        ui.label("Seed");
+        let seed = self.seed;
+        let text = self.seed_text.get_or_insert_with(|| seed.to_string());
+        let valid = text.trim().parse::<u64>().ok();
+        let mut edit = egui::TextEdit::singleline(text).desired_width(100.0);
+        if valid.is_none() {
+            edit = edit.text_color(egui::Color32::RED);
+        }
+        ui.add(edit)
+            .on_hover_text("The same seed always gives the same results from the same stone");
+        if let Some(seed) = valid {
+            self.seed = seed;
+        }
+        if ui.button("New seed").clicked() {
+            self.seed = new_seed();
+            self.seed_text = None;
+        }
+    }
+
+    pub(in crate::app) fn show(
+        &mut self,
+        ui: &mut Ui,
+        outcomes: Option<Published<Vec<SimResult>>>,
+        choice_outcomes: Option<Published<Vec<ChoiceOutcomes>>>,
+        policy_outcomes: Option<Published<Vec<PolicyOutcomes>>>,
+        goal: Goal,
+    ) -> SimMethod {
         ui.vertical(|ui| {
-            ui.heading("Most Likely Outcomes");
+            ui.heading("Outcomes");
             ui.horizontal(|ui| {
-                ui.label("Simulation runs");
-                egui::ComboBox::from_id_source("simulation-runs").show_index(
-                    ui,
-                    &mut self.index,
-                    PRESET_TRIES.len(),
-                    |i| PRESET_TRIES[i].to_string(),
-                );
+                ui.checkbox(&mut self.exact, "Exact");
+                if !self.exact {
+                    ui.checkbox(&mut self.adaptive, "Until precise")
+                        .on_hover_text("Keep simulating until the results are precise enough");
+                }
             });
+            if !self.exact {
+                ui.horizontal(|ui| {
+                    if self.adaptive {
+                        ui.label("Interval width under");
+                        egui::ComboBox::from_id_source("simulation-width").show_index(
+                            ui,
+                            &mut self.width_index,
+                            PRESET_WIDTHS.len(),
+                            |i| format!("{}%", 100.0 * PRESET_WIDTHS[i]),
+                        );
+                        ui.label("Time limit");
+                        egui::ComboBox::from_id_source("simulation-seconds").show_index(
+                            ui,
+                            &mut self.seconds_index,
+                            PRESET_SECONDS.len(),
+                            |i| format!("{} s", PRESET_SECONDS[i]),
+                        );
+                    } else {
+                        ui.label("Simulation runs");
+                        egui::ComboBox::from_id_source("simulation-runs").show_index(
+                            ui,
+                            &mut self.index,
+                            PRESET_TRIES.len(),
+                            |i| PRESET_TRIES[i].to_string(),
+                        );
+                    }
+                });
+                ui.horizontal(|ui| self.show_seed(ui));
+            }
 
-            if let Some(most_likely) = most_likely {
-                ui.vertical(|ui| {
-                    egui::Grid::new("sim-results-grid").show(ui, |ui| {
-                        ui.label("Skill 1");
-                        ui.label("Skill 2");
-                        ui.label("Negative");
-                        ui.label("Probability");
-                        ui.label("Final Score");
-                        ui.end_row();
-
-                        for result in most_likely {
-                            ui.label(format!("+{}", result.counts[0]));
-                            ui.label(format!("+{}", result.counts[1]));
-                            ui.label(format!("+{}", result.counts[2]));
-                            ui.label(format!("{:.2}%", 100.0 * result.probability));
-                            ui.label(format!("{:.3}", result.score));
-                            ui.end_row();
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.view, View::Outcomes, "Every result");
+                for (row, label) in ROW_LABELS.iter().enumerate() {
+                    ui.selectable_value(&mut self.view, View::Row(row), *label);
+                }
+                ui.selectable_value(&mut self.view, View::Choices, "By choice")
+                    .on_hover_text("Compare what each click from here could lead to");
+                ui.selectable_value(&mut self.view, View::Policies, "Strategies")
+                    .on_hover_text("Compare the solver with rules of thumb from here on");
+            });
+            match self.view {
+                View::Outcomes => {
+                    show_published(ui, outcomes, |ui, outcomes| {
+                        self.show_outcomes(ui, outcomes)
+                    });
+                }
+                View::Row(row) => {
+                    show_published(ui, outcomes, |ui, outcomes| {
+                        show_row(ui, "sim-row", outcomes, row)
+                    });
+                }
+                View::Choices => {
+                    ui.horizontal(|ui| {
+                        ui.label("Compare");
+                        ui.selectable_value(&mut self.choice_row, None, "Final score");
+                        for (row, label) in ROW_LABELS.iter().enumerate() {
+                            ui.selectable_value(&mut self.choice_row, Some(row), *label);
                         }
                     });
+                    let row = self.choice_row;
+                    show_published(ui, choice_outcomes, |ui, choices| {
+                        show_choices(ui, choices, row)
+                    });
+                }
+                View::Policies => {
+                    show_published(ui, policy_outcomes, |ui, policies| {
+                        self.show_policies(ui, policies, goal)
+                    });
+                }
+            }
+        });
+
+        self.method()
+    }
+
+    /// Shows every result in a table that sorts by whichever header was
+    /// clicked last.
+    fn show_outcomes(&mut self, ui: &mut Ui, outcomes: &[SimResult]) {
+        let sampled = outcomes.iter().any(|r| r.interval.is_some());
+        let most_likely = outcomes.iter().map(|r| r.probability).fold(0.0, f64::max);
+        let mut sorted = outcomes.iter().collect::<Vec<_>>();
+        // Stable, so ties stay most likely first.
+        sorted.sort_by(|a, b| {
+            let order = match self.sort_by {
+                Column::Successes(row) => a.counts[row].cmp(&b.counts[row]),
+                Column::Probability => a.probability.total_cmp(&b.probability),
+                Column::Score => a.score.total_cmp(&b.score),
+            };
+            if self.ascending {
+                order
+            } else {
+                order.reverse()
+            }
+        });
+
+        egui::ScrollArea::vertical()
+            .id_source("sim-results-scroll")
+            .max_height(300.0)
+            .show(ui, |ui| {
+                egui::Grid::new("sim-results-grid").show(ui, |ui| {
+                    for (row, label) in ROW_LABELS.iter().enumerate() {
+                        self.sort_header(ui, Column::Successes(row), label);
+                    }
+                    self.sort_header(ui, Column::Probability, "Probability");
+                    ui.label("");
+                    if sampled {
+                        ui.label("95% Interval")
+                            .on_hover_text("The true probability is in this range 95% of the time");
+                    }
+                    self.sort_header(ui, Column::Score, "Final Score");
+                    ui.end_row();
+
+                    for result in sorted {
+                        ui.label(format!("+{}", result.counts[0]));
+                        ui.label(format!("+{}", result.counts[1]));
+                        ui.label(format!("+{}", result.counts[2]));
+                        ui.label(format!("{:.2}%", 100.0 * result.probability));
+                        bar(ui, result.probability / most_likely);
+                        if sampled {
+                            ui.label(match result.interval {
+                                Some((low, high)) => {
+                                    format!("{:.2}% - {:.2}%", 100.0 * low, 100.0 * high)
+                                }
+                                None => String::new(),
+                            });
+                        }
+                        ui.label(format!("{:.3}", result.score));
+                        ui.end_row();
+                    }
                 });
+            });
+    }
+
+    /// Shows the average final score of each policy and how often it reaches
+    /// `goal`, next to the solver's, then every final score of the selected
+    /// one.
+    fn show_policies(&mut self, ui: &mut Ui, policies: &[PolicyOutcomes], goal: Goal) {
+        let stats = policies
+            .iter()
+            .map(|policy| {
+                let (mean, std_dev) = mean_and_std_dev(&policy.outcomes);
+                let reached = policy
+                    .outcomes
+                    .iter()
+                    .filter(|r| goal.is_met(r.counts))
+                    .map(|r| r.probability)
+                    .sum::<f64>();
+                (mean, std_dev, reached)
+            })
+            .collect::<Vec<_>>();
+        let solver = policies
+            .iter()
+            .position(|p| p.policy == Policy::Optimal)
+            .map(|i| stats[i]);
+
+        let goal_text = format!(
+            "Chance of at least +{} and +{} with at most +{} negative; \
+             set under \"Reach a goal\"",
+            goal.min_skills[0], goal.min_skills[1], goal.max_negative
+        );
+        egui::Grid::new("policies-grid").show(ui, |ui| {
+            ui.label("Strategy");
+            ui.label("Average");
+            ui.label("vs. Solver");
+            ui.label("Reaches Goal").on_hover_text(goal_text);
+            ui.label("vs. Solver");
+            ui.end_row();
+
+            for (i, (policy, &(mean, std_dev, reached))) in policies.iter().zip(&stats).enumerate()
+            {
+                let mut label =
+                    ui.selectable_label(self.policy_index == i, policy.policy.to_string());
+                if policy.outcomes.iter().any(|r| r.interval.is_some()) {
+                    label = label.on_hover_text("Estimated from simulated runs");
+                }
+                if label.clicked() {
+                    self.policy_index = i;
+                }
+                ui.label(format!("{:.3} \u{b1} {:.3}", mean, std_dev));
+                match solver {
+                    Some((solver_mean, _, _)) => ui.label(format!("{:+.3}", mean - solver_mean)),
+                    None => ui.label(""),
+                };
+                ui.label(format!("{:.2}%", 100.0 * reached));
+                match solver {
+                    Some((_, _, solver_reached)) => {
+                        ui.label(format!("{:+.2}%", 100.0 * (reached - solver_reached)))
+                    }
+                    None => ui.label(""),
+                };
+                ui.end_row();
+            }
+        });
+
+        if let Some(policy) = policies.get(self.policy_index) {
+            ui.separator();
+            ui.strong(policy.policy.to_string());
+            show_scores(ui, ("policy-scores", self.policy_index), &policy.outcomes);
+        }
+    }
+
+    /// A column header that sorts by `column` when clicked, or reverses the
+    /// order if it already does.
+    fn sort_header(&mut self, ui: &mut Ui, column: Column, label: &str) {
+        let selected = self.sort_by == column;
+        let text = match (selected, self.ascending) {
+            (true, true) => format!("{} \u{2b06}", label),
+            (true, false) => format!("{} \u{2b07}", label),
+            (false, _) => label.to_string(),
+        };
+        if ui.selectable_label(selected, text).clicked() {
+            if selected {
+                self.ascending = !self.ascending;
+            } else {
+                self.sort_by = column;
+                self.ascending = false;
+            }
+        }
+    }
+}
+
+/// Shows `published`, greyed out if it's out of date.
+fn show_published<T>(
+    ui: &mut Ui,
+    published: Option<Published<T>>,
+    add_contents: impl FnOnce(&mut Ui, &T),
+) {
+    if let Some(Published { value, current }) = published {
+        ui.vertical(|ui| {
+            if !current {
+                ui.colored_label(egui::Color32::GRAY, "Out of date; updating...");
             }
+            ui.set_enabled(current);
+            add_contents(ui, &value);
         });
+    }
+}
+
+/// Shows the final score distribution after each choice side by side, or
+/// how many successes `row` ends with after each if there is one.
+fn show_choices(ui: &mut Ui, choices: &[ChoiceOutcomes], row: Option<usize>) {
+    if choices.is_empty() {
+        ui.label("The stone is finished.");
+        return;
+    }
+    ui.horizontal_top(|ui| {
+        for choice in choices {
+            ui.group(|ui| {
+                ui.vertical(|ui| show_choice(ui, choice, row));
+            });
+        }
+    });
+}
 
-        PRESET_TRIES[self.index]
+fn show_choice(ui: &mut Ui, choice: &ChoiceOutcomes, row: Option<usize>) {
+    ui.strong(format!("Click {}", ROW_LABELS[choice.index]));
+    match row {
+        None => show_scores(ui, ("choice-scores", choice.index), &choice.outcomes),
+        Some(row) => show_row(ui, ("choice-row", choice.index), &choice.outcomes, row),
     }
 }
+
+/// Mean and standard deviation of the final score over `outcomes`.
+fn mean_and_std_dev(outcomes: &[SimResult]) -> (f64, f64) {
+    let mean = outcomes
+        .iter()
+        .map(|r| r.probability * r.score)
+        .sum::<f64>();
+    let variance = outcomes
+        .iter()
+        .map(|r| r.probability * (r.score - mean).powi(2))
+        .sum::<f64>();
+    (mean, variance.sqrt())
+}
+
+/// Shows the average final score over `outcomes` and how likely each
+/// distinct score is.
+fn show_scores(ui: &mut Ui, id_source: impl Hash + Copy, outcomes: &[SimResult]) {
+    let (mean, std_dev) = mean_and_std_dev(outcomes);
+    ui.label(format!("Average {:.3} \u{b1} {:.3}", mean, std_dev));
+
+    let scores = score_distribution(outcomes);
+    let most_likely = scores.iter().map(|&(_, p)| p).fold(0.0, f64::max);
+    egui::ScrollArea::vertical()
+        .id_source((id_source, "scroll"))
+        .max_height(300.0)
+        .show(ui, |ui| {
+            egui::Grid::new((id_source, "grid")).show(ui, |ui| {
+                ui.label("Final Score");
+                ui.label("Probability");
+                ui.label("");
+                ui.label("At least");
+                ui.end_row();
+
+                let mut at_least = 0.0;
+                for (score, p) in scores {
+                    at_least += p;
+                    ui.label(format!("{:.3}", score));
+                    ui.label(format!("{:.2}%", 100.0 * p));
+                    bar(ui, p / most_likely);
+                    ui.label(format!("{:.2}%", 100.0 * at_least));
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+/// Probability of each distinct final score among `outcomes`, best first.
+fn score_distribution(outcomes: &[SimResult]) -> Vec<(f64, f64)> {
+    let mut sorted = outcomes
+        .iter()
+        .map(|r| (r.score, r.probability))
+        .collect::<Vec<_>>();
+    sorted.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut scores: Vec<(f64, f64)> = Vec::new();
+    for (score, p) in sorted {
+        // Different results can score the same, give or take rounding.
+        match scores.last_mut() {
+            Some(last) if last.0 - score < 1e-9 => last.1 += p,
+            _ => scores.push((score, p)),
+        }
+    }
+    scores
+}
+
+/// Shows how likely `row` is to end with each number of successes, and with
+/// at least each number.
+fn show_row(ui: &mut Ui, id_source: impl Hash, outcomes: &[SimResult], row: usize) {
+    let exactly = marginal(outcomes, row);
+    let at_least = at_least(&exactly);
+    let most_likely = exactly.iter().copied().fold(0.0, f64::max);
+
+    egui::Grid::new(id_source).show(ui, |ui| {
+        ui.label("Successes");
+        ui.label("Exactly");
+        ui.label("");
+        ui.label("At least");
+        ui.label("");
+        ui.end_row();
+
+        for (count, (&p, &q)) in exactly.iter().zip(&at_least).enumerate() {
+            ui.label(format!("+{}", count));
+            ui.label(format!("{:.2}%", 100.0 * p));
+            bar(ui, p / most_likely);
+            ui.label(format!("{:.2}%", 100.0 * q));
+            bar(ui, q);
+            ui.end_row();
+        }
+    });
+}
+
+/// Probability of `row` ending with each number of successes, from every
+/// final result.
+fn marginal(outcomes: &[SimResult], row: usize) -> Vec<f64> {
+    let max = outcomes.iter().map(|r| r.counts[row]).max().unwrap_or(0);
+    let mut exactly = vec![0.0; usize::from(max) + 1];
+    for result in outcomes {
+        exactly[usize::from(result.counts[row])] += result.probability;
+    }
+    exactly
+}
+
+/// Probability of at least each number of successes, given the probability
+/// of exactly each number.
+fn at_least(exactly: &[f64]) -> Vec<f64> {
+    let mut at_least = exactly.to_vec();
+    for i in (0..at_least.len().saturating_sub(1)).rev() {
+        at_least[i] += at_least[i + 1];
+    }
+    at_least
+}
+
+/// Draws a bar `fraction` of the way across.
+fn bar(ui: &mut Ui, fraction: f64) {
+    let height = ui.spacing().interact_size.y * 0.6;
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(BAR_WIDTH, height), egui::Sense::hover());
+    let mut filled = rect;
+    filled.set_width(BAR_WIDTH * fraction.clamp(0.0, 1.0) as f32);
+    ui.painter()
+        .rect_filled(filled, 0.0, ui.visuals().selection.bg_fill);
+}
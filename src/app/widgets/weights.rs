@@ -1,71 +1,83 @@
-use crate::app::solution::Scoring;
+use crate::solver::{
+    GameState, Goal, Preset, Risk, Scoring, Solution, MAX_SLOTS, PRESETS, TABLE_LEN,
+};
 use eframe::egui::{self, epaint, Ui, Vec2};
 
-#[derive(Debug, PartialEq)]
-struct Preset {
-    name: &'static str,
-    scoring: Scoring,
-}
+// Largest per-row threshold offered in the goal editor.
+const MAX_GOAL: u8 = MAX_SLOTS;
 
-const PRESETS: [Preset; 6] = [
-    Preset {
-        name: "Balanced; slightly prefer skill 1",
-        scoring: Scoring {
-            success: [1.1, 1.0, -1.0],
-            fail: [0.0, 0.0, 0.0],
-        },
-    },
-    Preset {
-        name: "Balanced; slightly prefer skill 2",
-        scoring: Scoring {
-            success: [1.0, 1.1, -1.0],
-            fail: [0.0, 0.0, 0.0],
-        },
-    },
-    Preset {
-        name: "Maximize skill 1",
-        scoring: Scoring {
-            success: [100.0, 1.0, -1.0],
-            fail: [0.0, 0.0, 0.0],
-        },
-    },
-    Preset {
-        name: "Maximize skill 2",
-        scoring: Scoring {
-            success: [1.0, 100.0, -1.0],
-            fail: [0.0, 0.0, 0.0],
-        },
-    },
-    Preset {
-        name: "Minimize negative; slightly prefer skill 1",
-        scoring: Scoring {
-            success: [1.1, 1.0, -100.0],
-            fail: [0.0, 0.0, 100.0],
-        },
-    },
-    Preset {
-        name: "Minimize negative; slightly prefer skill 2",
-        scoring: Scoring {
-            success: [1.0, 1.1, -100.0],
-            fail: [0.0, 0.0, 100.0],
-        },
-    },
-];
+// Solutions with bigger tables than this are slow to build and may not fit
+// in a browser tab, so they get a warning.
+const LARGE_SOLUTION_BYTES: usize = 64 << 20;
 
 #[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "persistence", serde(default))] // if we add new fields, give them default values when deserializing old state
 pub(in crate::app) struct Weights {
+    mode: Mode,
     success: [String; 3],
     fail: [String; 3],
+    // minimum skill 1, minimum skill 2, maximum negative
+    goal: [u8; 3],
+    table: [[f64; TABLE_LEN]; 3],
+    risk: RiskMode,
+    lambda: f64,
+    cvar_percent: u8,
+}
+
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Weighted,
+    Goal,
+    Table,
+}
+
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RiskMode {
+    Neutral,
+    MeanStdDev,
+    Cvar,
 }
 
+impl RiskMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            RiskMode::Neutral => "Expected value",
+            RiskMode::MeanStdDev => "Mean - \u{3bb}\u{b7}stddev",
+            RiskMode::Cvar => "CVaR (average of worst outcomes)",
+        }
+    }
+}
+
+const ALL_RISK_MODES: [RiskMode; 3] = [RiskMode::Neutral, RiskMode::MeanStdDev, RiskMode::Cvar];
+
+// Engravings pay off at 5, 7, 9 and 10 nodes; a negative engraving only hurts
+// from 5 nodes on.
+const DEFAULT_SKILL_PAYOFF: [f64; TABLE_LEN] = [
+    0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 2.0, 2.0, 3.0, 4.0, 4.0, 4.0, 4.0, 4.0, 4.0, 4.0,
+];
+const DEFAULT_NEGATIVE_PAYOFF: [f64; TABLE_LEN] = [
+    0.0, 0.0, 0.0, 0.0, 0.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0,
+];
+
 impl Default for Weights {
     fn default() -> Self {
         let mut this = Self {
+            mode: Mode::Weighted,
             success: Default::default(),
             fail: Default::default(),
+            goal: [7, 7, 4],
+            table: [
+                DEFAULT_SKILL_PAYOFF,
+                DEFAULT_SKILL_PAYOFF,
+                DEFAULT_NEGATIVE_PAYOFF,
+            ],
+            risk: RiskMode::Neutral,
+            lambda: 0.5,
+            cvar_percent: 25,
         };
-        this.assign_to_preset(&PRESETS[0]);
+        this.set_scoring(PRESETS[0].scoring);
         this
     }
 }
@@ -98,15 +110,20 @@ const INVALID_FRAME: egui::Frame = egui::Frame {
     },
 };
 
+/// The weight typed in `s`, if it's a finite number.
+fn parse_weight(s: &str) -> Option<f64> {
+    s.trim().parse::<f64>().ok().filter(|x| x.is_finite())
+}
+
 fn show_textedit(ui: &mut Ui, s: &mut String) -> Option<f64> {
-    match s.trim().parse::<f64>() {
-        Ok(x) if x.is_finite() => {
+    match parse_weight(s) {
+        Some(x) => {
             VALID_FRAME.show(ui, |ui| {
                 ui.text_edit_singleline(s);
             });
             Some(x)
         }
-        _ => {
+        None => {
             INVALID_FRAME.show(ui, |ui| {
                 ui.text_edit_singleline(s);
             });
@@ -116,7 +133,7 @@ fn show_textedit(ui: &mut Ui, s: &mut String) -> Option<f64> {
 }
 
 fn parsed_fields_to_scoring(success: [Option<f64>; 3], fail: [Option<f64>; 3]) -> Option<Scoring> {
-    Some(Scoring {
+    Some(Scoring::Weighted {
         success: [success[0]?, success[1]?, success[2]?],
         fail: [fail[0]?, fail[1]?, fail[2]?],
     })
@@ -127,85 +144,258 @@ impl Weights {
         &mut self,
         ui: &mut Ui,
         selected_preset: &mut usize,
+        saved_presets: &[Preset],
     ) -> Option<Scoring> {
         let mut scoring = None;
         ui.vertical(|ui| {
-            let mut success = [None; 3];
-            let mut fail = [None; 3];
             ui.heading("Weights");
-            egui::Grid::new("weights-grid")
-                .min_col_width(72.0)
-                .show(ui, |ui| {
-                    ui.label("");
-                    ui.label("Success");
-                    ui.label("Fail");
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.mode, Mode::Weighted, "Point values");
+                ui.radio_value(&mut self.mode, Mode::Goal, "Reach a goal");
+                ui.radio_value(&mut self.mode, Mode::Table, "Payoff table");
+            });
+
+            scoring = match self.mode {
+                Mode::Weighted => self.show_weighted(ui),
+                Mode::Goal => Some(self.show_goal(ui)),
+                Mode::Table => Some(self.show_table(ui)),
+            };
+            self.show_presets(ui, &mut scoring, selected_preset, saved_presets);
+
+            // A goal's score is already a probability; there's no spread to
+            // trade off against it.
+            if self.mode != Mode::Goal {
+                self.show_risk(ui);
+            }
+        });
+
+        scoring
+    }
+
+    fn show_weighted(&mut self, ui: &mut Ui) -> Option<Scoring> {
+        let mut success = [None; 3];
+        let mut fail = [None; 3];
+        egui::Grid::new("weights-grid")
+            .min_col_width(72.0)
+            .show(ui, |ui| {
+                ui.label("");
+                ui.label("Success");
+                ui.label("Fail");
+                ui.end_row();
+
+                for (i, &s) in ["Skill 1", "Skill 2", "Negative"].iter().enumerate() {
+                    ui.label(s);
+                    success[i] = show_textedit(ui, &mut self.success[i]);
+                    fail[i] = show_textedit(ui, &mut self.fail[i]);
                     ui.end_row();
+                }
+            });
 
-                    for (i, &s) in ["Skill 1", "Skill 2", "Negative"].iter().enumerate() {
-                        ui.label(s);
-                        success[i] = show_textedit(ui, &mut self.success[i]);
-                        fail[i] = show_textedit(ui, &mut self.fail[i]);
-                        ui.end_row();
-                    }
+        parsed_fields_to_scoring(success, fail)
+    }
+
+    /// Shows the built-in and saved presets; picking one replaces `scoring`.
+    fn show_presets(
+        &mut self,
+        ui: &mut Ui,
+        scoring: &mut Option<Scoring>,
+        selected_preset: &mut usize,
+        saved_presets: &[Preset],
+    ) {
+        let presets = || PRESETS.iter().chain(saved_presets);
+        let num_presets = PRESETS.len() + saved_presets.len();
+        if let Some(scoring) = scoring.as_ref() {
+            // Update presets combo box to match current weights
+            *selected_preset = presets()
+                .position(|preset| preset.scoring == *scoring)
+                .unwrap_or(num_presets);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Presets");
+            let resp = egui::ComboBox::from_id_source("presets-combo")
+                .width(300.0)
+                .show_index(ui, selected_preset, num_presets + 1, |i| {
+                    presets()
+                        .nth(i)
+                        .map(|p| p.name.to_string())
+                        .unwrap_or_else(|| "Custom".to_string())
                 });
+            if resp.changed() {
+                if let Some(preset) = presets().nth(*selected_preset) {
+                    self.set_scoring(preset.scoring);
+                    *scoring = Some(preset.scoring);
+                }
+            }
+        });
+    }
 
-            scoring = parsed_fields_to_scoring(success, fail);
-            if let Some(scoring) = scoring.as_ref() {
-                // Update presets combo box to match current weights
-                let mut found_preset = false;
-                for (i, preset) in PRESETS.iter().enumerate() {
-                    if preset.scoring == *scoring {
-                        *selected_preset = i;
-                        found_preset = true;
-                        break;
-                    }
+    fn show_goal(&mut self, ui: &mut Ui) -> Scoring {
+        egui::Grid::new("goal-grid")
+            .min_col_width(72.0)
+            .show(ui, |ui| {
+                for (i, &s) in ["Skill 1 at least", "Skill 2 at least", "Negative at most"]
+                    .iter()
+                    .enumerate()
+                {
+                    ui.label(s);
+                    ui.add(egui::DragValue::new(&mut self.goal[i]).clamp_range(0..=MAX_GOAL));
+                    ui.end_row();
                 }
-                if !found_preset {
-                    *selected_preset = PRESETS.len();
+            });
+
+        self.goal_scoring()
+    }
+
+    fn show_table(&mut self, ui: &mut Ui) -> Scoring {
+        ui.label("Value of finishing with each number of successes");
+        egui::ScrollArea::horizontal().show(ui, |ui| {
+            egui::Grid::new("payoff-table-grid").show(ui, |ui| {
+                ui.label("");
+                for n in 0..TABLE_LEN {
+                    ui.label(format!("+{}", n));
                 }
-            }
+                ui.end_row();
 
-            ui.horizontal(|ui| {
-                ui.label("Presets");
-                let resp = egui::ComboBox::from_id_source("presets-combo")
-                    .width(300.0)
-                    .show_index(ui, selected_preset, PRESETS.len() + 1, |i| {
-                        PRESETS
-                            .get(i)
-                            .map(|p| p.name.to_string())
-                            .unwrap_or_else(|| "Custom".to_string())
-                    });
-                if resp.changed() {
-                    println!("changed to {}", selected_preset);
-                    if let Some(preset) = PRESETS.get(*selected_preset) {
-                        self.assign_to_preset(preset);
-                        scoring = Some(preset.scoring);
+                for (&s, row) in ["Skill 1", "Skill 2", "Negative"]
+                    .iter()
+                    .zip(&mut self.table)
+                {
+                    ui.label(s);
+                    for value in row {
+                        ui.add(egui::DragValue::new(value).speed(0.1));
                     }
+                    ui.end_row();
                 }
             });
         });
 
-        scoring
+        Scoring::Table { payoff: self.table }
     }
 
-    fn assign_to_preset(&mut self, preset: &Preset) {
-        for i in 0..3 {
-            self.success[i] = format!("{:.1}", preset.scoring.success[i]);
-            self.fail[i] = format!("{:.1}", preset.scoring.fail[i]);
+    fn show_risk(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Risk");
+            egui::ComboBox::from_id_source("risk-combo")
+                .width(200.0)
+                .selected_text(self.risk.as_str())
+                .show_ui(ui, |ui| {
+                    for mode in ALL_RISK_MODES {
+                        ui.selectable_value(&mut self.risk, mode, mode.as_str());
+                    }
+                });
+            match self.risk {
+                RiskMode::Neutral => {}
+                RiskMode::MeanStdDev => {
+                    ui.label("\u{3bb}");
+                    ui.add(
+                        egui::DragValue::new(&mut self.lambda)
+                            .speed(0.01)
+                            .clamp_range(0.0..=10.0),
+                    );
+                }
+                RiskMode::Cvar => {
+                    ui.label("Worst");
+                    ui.add(
+                        egui::DragValue::new(&mut self.cvar_percent)
+                            .clamp_range(1..=100)
+                            .suffix("%"),
+                    );
+                }
+            }
+        });
+    }
+
+    pub(in crate::app) fn risk(&self) -> Risk {
+        if self.mode == Mode::Goal {
+            return Risk::Neutral;
+        }
+        match self.risk {
+            RiskMode::Neutral => Risk::Neutral,
+            RiskMode::MeanStdDev => Risk::MeanStdDev {
+                lambda: self.lambda,
+            },
+            RiskMode::Cvar => Risk::Cvar {
+                alpha: f64::from(self.cvar_percent) / 100.0,
+            },
+        }
+    }
+
+    /// Warns if solving `scoring` with these risk settings for the stone in
+    /// `game_state` takes a lot of memory.
+    pub(in crate::app) fn show_size_warning(
+        &self,
+        ui: &mut Ui,
+        scoring: Scoring,
+        game_state: &GameState,
+    ) {
+        let bytes = Solution::memory_needed(
+            scoring,
+            self.risk(),
+            *game_state.ladder(),
+            game_state.num_slots(),
+        );
+        if bytes > LARGE_SOLUTION_BYTES {
+            ui.colored_label(
+                egui::Color32::RED,
+                format!(
+                    "Solving this for {} slots needs over {} MB and may take a while",
+                    game_state.num_slots(),
+                    bytes >> 20
+                ),
+            );
+        }
+    }
+
+    /// The goal set under "Reach a goal", even when scoring by points.
+    pub(in crate::app) fn goal(&self) -> Goal {
+        Goal {
+            min_skills: [self.goal[0], self.goal[1]],
+            max_negative: self.goal[2],
+        }
+    }
+
+    fn goal_scoring(&self) -> Scoring {
+        Scoring::Goal(self.goal())
+    }
+
+    /// Switches to whichever mode `scoring` belongs to and fills it in.
+    pub(in crate::app) fn set_scoring(&mut self, scoring: Scoring) {
+        match scoring {
+            Scoring::Weighted { success, fail } => {
+                self.mode = Mode::Weighted;
+                for i in 0..3 {
+                    self.success[i] = success[i].to_string();
+                    self.fail[i] = fail[i].to_string();
+                }
+            }
+            Scoring::Goal(goal) => {
+                self.mode = Mode::Goal;
+                self.goal = [goal.min_skills[0], goal.min_skills[1], goal.max_negative];
+            }
+            Scoring::Table { payoff } => {
+                self.mode = Mode::Table;
+                self.table = payoff;
+            }
         }
     }
 
     pub(in crate::app) fn parse(&self) -> Option<Scoring> {
+        match self.mode {
+            Mode::Weighted => {}
+            Mode::Goal => return Some(self.goal_scoring()),
+            Mode::Table => return Some(Scoring::Table { payoff: self.table }),
+        }
         parsed_fields_to_scoring(
             [
-                self.success[0].parse().ok(),
-                self.success[1].parse().ok(),
-                self.success[2].parse().ok(),
+                parse_weight(&self.success[0]),
+                parse_weight(&self.success[1]),
+                parse_weight(&self.success[2]),
             ],
             [
-                self.fail[0].parse().ok(),
-                self.fail[1].parse().ok(),
-                self.fail[2].parse().ok(),
+                parse_weight(&self.fail[0]),
+                parse_weight(&self.fail[1]),
+                parse_weight(&self.fail[2]),
             ],
         )
     }
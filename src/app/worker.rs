@@ -0,0 +1,1396 @@
+//! The solver engine behind the UI, and the messages it trades with it.
+//!
+//! The UI sends [`Request`]s through a [`ThreadHandle`]; an [`Engine`] on
+//! the other side of a [`Transport`] applies them and answers with
+//! [`Response`]s, which the handle collects in [`Results`]. The engine runs
+//! on its own thread natively and in a Web Worker on the web.
+//!
+//! Every request is numbered, and every result says which request it
+//! answers, so the UI can tell when what it shows is out of date.
+//!
+//! Once it's caught up, the native engine builds solutions for other slot
+//! counts and presets in the background, so switching to them is instant. A
+//! Web Worker doesn't: it can't hear new requests while it builds, so they
+//! would wait on solutions nobody asked for. For the same reason, it does
+//! adaptive simulations a slice at a time, hearing requests in between.
+//!
+//! The handle also keeps an eye on the engine: if it dies, it's replaced by
+//! a new one given the same inputs.
+
+mod cache;
+
+use self::cache::{Key, SolutionCache};
+use super::widgets::ALL_NUM_SLOTS;
+use super::worker_thread::Client;
+use super::SimMethod;
+use crate::solver::{
+    Answer, BuildError, Cancelled, GameState, Policy, Risk, Scoring, SimResult, SimTally, Solution,
+    SuccessRates, POLICIES, PRESETS,
+};
+use arrayvec::ArrayVec;
+use eframe::epi::RepaintSignal;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+#[cfg(any(target_arch = "wasm32", test))]
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fmt, iter, rc::Rc, sync::Arc};
+
+// Progress changes smaller than this aren't worth a response.
+const PROGRESS_STEP: f32 = 0.01;
+
+// Runs between checks on an adaptive simulation.
+const ADAPTIVE_BATCH: u32 = 4096;
+
+// Seconds between sending what an adaptive simulation has so far.
+const PUBLISH_EVERY: f64 = 0.25;
+
+// Seconds of adaptive simulation a Web Worker does before it stops to hear
+// new requests. A thread hears them as it goes.
+const SLICE_SECONDS: Option<f64> = if cfg!(target_arch = "wasm32") {
+    Some(0.1)
+} else {
+    None
+};
+
+// Runs to estimate how random clicking does from, and the seed they're
+// drawn with.
+const POLICY_RUNS: u32 = 100_000;
+const POLICY_SEED: u64 = 0;
+
+/// A change to what the engine should solve.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[allow(clippy::large_enum_variant)]
+pub(super) enum Request {
+    Weights(Scoring),
+    Risk(Risk),
+    Rates(SuccessRates),
+    SimMethod(SimMethod),
+    GameState(GameState),
+    /// Whether to compare the solver with the other policies, which is only
+    /// worth the time while someone is looking.
+    ComparePolicies(bool),
+}
+
+impl Request {
+    /// Whether a solution for `game_state` is out of date after this request.
+    fn changes_solution(&self, game_state: &GameState) -> bool {
+        match self {
+            Request::Weights(_) | Request::Risk(_) | Request::Rates(_) => true,
+            Request::SimMethod(_) | Request::ComparePolicies(_) => false,
+            Request::GameState(new) => !new.same_rules(game_state),
+        }
+    }
+
+    /// Whether results worked out before this request are out of date after
+    /// it.
+    fn changes_results(&self) -> bool {
+        !matches!(self, Request::ComparePolicies(_))
+    }
+}
+
+/// A request or result and the generation it belongs to. The UI numbers
+/// requests in the order it sends them; a result belongs to the last request
+/// applied before it was worked out.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub(super) struct Tagged<T> {
+    pub(super) generation: u32,
+    pub(super) value: T,
+}
+
+/// A result from the engine, and whether it answers the latest request.
+#[derive(Debug, Clone)]
+pub(in crate::app) struct Published<T> {
+    pub(in crate::app) value: T,
+    pub(in crate::app) current: bool,
+}
+
+/// Every final result if one row is clicked next and every later click is
+/// the best one.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub(in crate::app) struct ChoiceOutcomes {
+    pub(in crate::app) index: usize,
+    /// Most likely first.
+    pub(in crate::app) outcomes: Vec<SimResult>,
+}
+
+/// Every final result if every click from now on is picked by one policy.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub(in crate::app) struct PolicyOutcomes {
+    pub(in crate::app) policy: Policy,
+    /// Most likely first.
+    pub(in crate::app) outcomes: Vec<SimResult>,
+}
+
+/// What the engine has to say.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub(super) enum Response {
+    Status(Status),
+    /// Fraction of the current build or simulation done so far.
+    Progress(f32),
+    /// The choices from the current game state.
+    Choices(Tagged<Option<ArrayVec<Answer, 3>>>),
+    /// Every final result from the current game state, most likely first.
+    SimResults(Tagged<Vec<SimResult>>),
+    /// Outcomes of each choice from the current game state, in row order.
+    ChoiceOutcomes(Tagged<Vec<ChoiceOutcomes>>),
+    /// Outcomes of each policy in `POLICIES` from the current game state, in
+    /// order.
+    PolicyOutcomes(Tagged<Vec<PolicyOutcomes>>),
+}
+
+/// What the engine is up to.
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+pub(super) enum Status {
+    /// The weights don't parse, so there's nothing to solve.
+    Waiting,
+    #[default]
+    Solving,
+    /// The inputs can't be solved.
+    Failed(BuildError),
+    Solved {
+        num_states: usize,
+        simulating: bool,
+    },
+}
+
+impl Status {
+    /// Whether the engine is working on something.
+    pub(super) fn is_busy(self) -> bool {
+        matches!(
+            self,
+            Status::Solving
+                | Status::Solved {
+                    simulating: true,
+                    ..
+                }
+        )
+    }
+
+    /// Whether the engine needs different inputs to get anywhere.
+    pub(super) fn is_error(self) -> bool {
+        matches!(self, Status::Waiting | Status::Failed(_))
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Waiting => write!(f, "Waiting for valid weights"),
+            Status::Solving => write!(f, "Finding solution..."),
+            Status::Failed(err) => write!(f, "Can't solve: {}", err),
+            Status::Solved {
+                num_states,
+                simulating: false,
+            } => write!(f, "Solved ({} states)", num_states),
+            Status::Solved {
+                num_states,
+                simulating: true,
+            } => write!(f, "Solved ({} states); running simulations...", num_states),
+        }
+    }
+}
+
+/// How requests reach an [`Engine`] and its responses get back.
+pub(super) trait Transport {
+    /// Waits for the next request. Returns `None` once no more can come.
+    fn recv(&mut self) -> Option<Tagged<Request>>;
+    /// The next request, if one is already waiting.
+    fn try_recv(&mut self) -> Option<Tagged<Request>>;
+    fn send(&mut self, response: Response);
+}
+
+/// Packs a request or response for the trip to or from a Web Worker.
+#[cfg(any(target_arch = "wasm32", test))]
+pub(super) fn encode(message: &impl Serialize) -> Vec<u8> {
+    bincode::serialize(message).expect("every message can be serialized")
+}
+
+/// Unpacks a message packed by [`encode`], or `None` if it's garbled.
+#[cfg(any(target_arch = "wasm32", test))]
+pub(super) fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Option<T> {
+    bincode::deserialize(bytes).ok()
+}
+
+/// Solves for the latest requests, giving up on work that newer requests
+/// make pointless.
+pub(super) struct Engine {
+    scoring: Option<Scoring>,
+    risk: Risk,
+    rates: SuccessRates,
+    sim_method: Option<SimMethod>,
+    game_state: GameState,
+    // The last request applied.
+    generation: u32,
+    solution: Option<Rc<Solution>>,
+    cache: SolutionCache,
+    // Solutions to build once there's nothing else to do, next last.
+    warm_queue: Vec<Key>,
+    // Whether to fill `warm_queue` at all.
+    warming: bool,
+    compare_policies: bool,
+    // How long to simulate before stopping to hear requests, if there's no
+    // hearing them meanwhile.
+    slice: Option<f64>,
+    // An adaptive simulation stopped at the end of a slice, to carry on.
+    paused: Option<AdaptiveRun>,
+    // Work left to do for requests applied so far.
+    solution_stale: bool,
+    simulation_stale: bool,
+    policies_stale: bool,
+}
+
+impl Engine {
+    /// An engine with nothing to solve yet. Unless `warming`, it only builds
+    /// the solutions it's asked for.
+    pub(super) fn new(warming: bool) -> Self {
+        Self {
+            scoring: None,
+            risk: Risk::Neutral,
+            rates: SuccessRates::advertised(),
+            sim_method: None,
+            game_state: GameState::default(),
+            generation: 0,
+            solution: None,
+            cache: SolutionCache::default(),
+            warm_queue: Vec::new(),
+            warming,
+            compare_policies: false,
+            slice: SLICE_SECONDS,
+            paused: None,
+            solution_stale: true,
+            simulation_stale: false,
+            policies_stale: false,
+        }
+    }
+
+    pub(super) fn apply(&mut self, request: Tagged<Request>) {
+        self.generation = request.generation;
+        if request.value.changes_solution(&self.game_state) {
+            self.solution_stale = true;
+        }
+        if request.value.changes_results() {
+            self.paused = None;
+        }
+        match request.value {
+            Request::Weights(scoring) => self.scoring = Some(scoring),
+            Request::Risk(risk) => self.risk = risk,
+            Request::Rates(rates) => self.rates = rates,
+            Request::SimMethod(sim_method) => {
+                self.sim_method = Some(sim_method);
+                self.simulation_stale = true;
+            }
+            Request::GameState(game_state) => {
+                self.game_state = game_state;
+                self.simulation_stale = true;
+            }
+            Request::ComparePolicies(compare) => self.compare_policies = compare,
+        }
+    }
+
+    /// Serves requests until `transport` runs out of them. A Web Worker is
+    /// driven by its message events instead.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(super) fn run(mut self, transport: &mut impl Transport) {
+        loop {
+            self.catch_up(transport);
+            match transport.recv() {
+                Some(request) => self.apply(request),
+                None => return,
+            }
+        }
+    }
+
+    /// Applies every waiting request, then does whatever work they leave.
+    /// Returns true if it stopped at the end of a slice, to be called again
+    /// once any new requests are applied.
+    pub(super) fn catch_up(&mut self, transport: &mut impl Transport) -> bool {
+        loop {
+            while let Some(request) = transport.try_recv() {
+                self.apply(request);
+            }
+            if self.solution_stale {
+                self.rebuild_solution(transport);
+            } else if self.simulation_stale {
+                self.rerun_simulation(transport);
+                if self.paused.is_some() {
+                    return true;
+                }
+            } else if self.policies_stale && self.compare_policies {
+                self.rerun_policies(transport);
+            } else if let Some(key) = self.warm_queue.pop() {
+                self.warm(key, transport);
+            } else {
+                return false;
+            }
+        }
+    }
+
+    fn rebuild_solution(&mut self, transport: &mut impl Transport) {
+        self.solution = None;
+        let scoring = match self.scoring {
+            Some(scoring) => scoring,
+            None => {
+                self.solution_stale = false;
+                transport.send(Response::Status(Status::Waiting));
+                return;
+            }
+        };
+        let key = Key {
+            scoring,
+            risk: self.risk,
+            ladder: *self.game_state.ladder(),
+            rates: self.rates.clone(),
+            num_slots: self.game_state.num_slots(),
+        };
+        if let Some(solution) = self.cache.get(&key) {
+            self.solution = Some(solution);
+            self.solution_stale = false;
+            self.simulation_stale = true;
+            self.queue_warming(&key);
+            return;
+        }
+        transport.send(Response::Status(Status::Solving));
+
+        // Requests that come in meanwhile are held until we're done; clicks
+        // on the same stone don't change its solution, so only stop for the
+        // others.
+        let mut deferred = Vec::new();
+        let mut last_sent = None;
+        let new_solution = key.build(|fraction| {
+            deferred.extend(iter::from_fn(|| transport.try_recv()));
+            send_progress(transport, &mut last_sent, fraction);
+            !deferred
+                .iter()
+                .any(|r: &Tagged<Request>| r.value.changes_solution(&self.game_state))
+        });
+
+        match new_solution {
+            Ok(new_solution) => {
+                let new_solution = Rc::new(new_solution);
+                self.cache.insert(key.clone(), Rc::clone(&new_solution));
+                self.solution = Some(new_solution);
+                self.solution_stale = false;
+                self.simulation_stale = true;
+                self.queue_warming(&key);
+            }
+            Err(BuildError::Cancelled) => {}
+            Err(err) => {
+                self.solution_stale = false;
+                transport.send(Response::Status(Status::Failed(err)));
+            }
+        }
+        for request in deferred {
+            self.apply(request);
+        }
+    }
+
+    /// Lines up solutions for every slot count with the scoring in `key`,
+    /// then every preset, keeping the rest of `key`.
+    fn queue_warming(&mut self, key: &Key) {
+        if !self.warming {
+            return;
+        }
+        let slot_counts = |scoring| {
+            ALL_NUM_SLOTS.iter().map(move |&(num_slots, _)| Key {
+                scoring,
+                num_slots,
+                ..key.clone()
+            })
+        };
+        let presets = PRESETS.iter().map(|preset| Key {
+            scoring: preset.scoring,
+            ..key.clone()
+        });
+        let mut queue: Vec<Key> = slot_counts(key.scoring)
+            .chain(presets)
+            .chain(
+                PRESETS
+                    .iter()
+                    .flat_map(|preset| slot_counts(preset.scoring)),
+            )
+            .filter(|k| !self.cache.contains(k))
+            .collect();
+        queue.reverse();
+        self.warm_queue = queue;
+    }
+
+    /// Builds the solution for `key` in the background, giving way to any
+    /// request.
+    fn warm(&mut self, key: Key, transport: &mut impl Transport) {
+        // Solutions the user asked for matter more than ones they might, so
+        // skip any that would push one out.
+        if self.cache.contains(&key) || !self.cache.has_room_for(key.memory_needed()) {
+            return;
+        }
+        let mut deferred = Vec::new();
+        let solution = key.build(|_| {
+            deferred.extend(iter::from_fn(|| transport.try_recv()));
+            deferred.is_empty()
+        });
+        match solution {
+            Ok(solution) => self.cache.insert(key, Rc::new(solution)),
+            Err(BuildError::Cancelled) => self.warm_queue.push(key),
+            // If it's ever asked for, the error will come up then.
+            Err(_) => {}
+        }
+        for request in deferred {
+            self.apply(request);
+        }
+    }
+
+    fn rerun_simulation(&mut self, transport: &mut impl Transport) {
+        self.simulation_stale = false;
+        self.policies_stale = true;
+        // Without a solution, we've already said why.
+        let solution = match self.solution.as_ref() {
+            Some(solution) => solution,
+            None => return,
+        };
+        let generation = self.generation;
+        let choices = solution.sorted_choices(&self.game_state);
+        // Rows to compare, in order.
+        let mut rows = choices
+            .iter()
+            .flatten()
+            .map(|answer| answer.index)
+            .collect::<ArrayVec<usize, 3>>();
+        rows.sort_unstable();
+        transport.send(Response::Choices(Tagged {
+            generation,
+            value: choices,
+        }));
+        let num_states = solution.num_states();
+        transport.send(Response::Status(Status::Solved {
+            num_states,
+            simulating: true,
+        }));
+
+        let mut deferred = Vec::new();
+        let mut last_sent = None;
+        let mut report = |fraction, response: Option<Response>| {
+            deferred.extend(iter::from_fn(|| transport.try_recv()));
+            if let Some(response) = response {
+                transport.send(response);
+            }
+            send_progress(transport, &mut last_sent, fraction);
+            !deferred
+                .iter()
+                .any(|r: &Tagged<Request>| r.value.changes_results())
+        };
+        // The simulation gets an equal share of the progress bar to each
+        // choice.
+        let steps = (rows.len() + usize::from(self.sim_method.is_some())).max(1) as f64;
+        let done = 1.0 - rows.len() as f64 / steps;
+
+        let outcomes = match self.sim_method {
+            None => Ok(None),
+            Some(SimMethod::Exact) => solution
+                .outcome_distribution_with_progress(&self.game_state, |fraction| {
+                    report(fraction / steps, None)
+                })
+                .map(|outcomes| Some(solution.rank(outcomes))),
+            Some(SimMethod::Sampled { tries, seed }) => solution
+                .simulate_with_progress(
+                    tries,
+                    &self.game_state,
+                    &mut ChaCha8Rng::seed_from_u64(seed),
+                    |fraction| report(fraction / steps, None),
+                )
+                .map(|tally| Some(solution.outcomes_of(&tally))),
+            Some(SimMethod::Adaptive {
+                width,
+                seconds,
+                seed,
+            }) => {
+                let mut run = self.paused.take().unwrap_or_else(|| AdaptiveRun::new(seed));
+                let (slice, sliced) = (Stopwatch::start(), self.slice);
+                let outcomes = simulate_adaptive(
+                    solution,
+                    &self.game_state,
+                    width,
+                    seconds,
+                    &mut run,
+                    |fraction, so_far| {
+                        let so_far = so_far.map(|so_far| {
+                            Response::SimResults(Tagged {
+                                generation,
+                                value: so_far.to_vec(),
+                            })
+                        });
+                        report(fraction / steps, so_far)
+                            && !sliced.is_some_and(|limit| slice.elapsed_secs() >= limit)
+                    },
+                );
+                // Kept to carry on with; requests that make it out of date
+                // drop it when they're applied.
+                if outcomes.is_err() {
+                    self.paused = Some(run);
+                }
+                outcomes.map(Some)
+            }
+        };
+        // The main results go out first; the other choices are a closer look.
+        let simulated = match outcomes {
+            Ok(outcomes) => report(
+                done,
+                outcomes.map(|outcomes| {
+                    Response::SimResults(Tagged {
+                        generation,
+                        value: outcomes,
+                    })
+                }),
+            ),
+            Err(Cancelled) => false,
+        };
+
+        let mut choice_outcomes = Vec::with_capacity(rows.len());
+        for (i, &index) in rows.iter().enumerate() {
+            if !simulated {
+                break;
+            }
+            let outcomes = solution.choice_outcome_distribution_with_progress(
+                &self.game_state,
+                index,
+                |fraction| report(done + (i as f64 + fraction) / steps, None),
+            );
+            match outcomes {
+                Ok(outcomes) => choice_outcomes.push(ChoiceOutcomes {
+                    index,
+                    outcomes: solution.rank(outcomes),
+                }),
+                Err(Cancelled) => break,
+            }
+        }
+
+        if simulated && choice_outcomes.len() == rows.len() {
+            transport.send(Response::ChoiceOutcomes(Tagged {
+                generation,
+                value: choice_outcomes,
+            }));
+            transport.send(Response::Status(Status::Solved {
+                num_states,
+                simulating: false,
+            }));
+        } else {
+            self.simulation_stale = true;
+        }
+        for request in deferred {
+            self.apply(request);
+        }
+    }
+
+    /// Works out how each policy in `POLICIES` does from the current game
+    /// state.
+    fn rerun_policies(&mut self, transport: &mut impl Transport) {
+        self.policies_stale = false;
+        let solution = match self.solution.as_ref() {
+            Some(solution) => solution,
+            None => return,
+        };
+        let generation = self.generation;
+        let num_states = solution.num_states();
+        transport.send(Response::Status(Status::Solved {
+            num_states,
+            simulating: true,
+        }));
+
+        // Any request at all makes these out of date or unwanted.
+        let mut deferred = Vec::new();
+        let mut last_sent = None;
+        let mut report = |fraction| {
+            deferred.extend(iter::from_fn(|| transport.try_recv()));
+            send_progress(transport, &mut last_sent, fraction);
+            deferred.is_empty()
+        };
+        let steps = POLICIES.len() as f64;
+        let mut policy_outcomes = Vec::with_capacity(POLICIES.len());
+        for (i, &policy) in POLICIES.iter().enumerate() {
+            let progress = |fraction| report((i as f64 + fraction) / steps);
+            let outcomes = match policy {
+                // Random clicks can reach far too many states to follow
+                // every one.
+                Policy::Random => solution
+                    .simulate_policy_with_progress(
+                        POLICY_RUNS,
+                        &self.game_state,
+                        policy,
+                        &mut ChaCha8Rng::seed_from_u64(POLICY_SEED),
+                        progress,
+                    )
+                    .map(|tally| solution.outcomes_of(&tally)),
+                _ => solution
+                    .policy_outcome_distribution_with_progress(&self.game_state, policy, progress)
+                    .map(|outcomes| solution.rank(outcomes)),
+            };
+            match outcomes {
+                Ok(outcomes) => policy_outcomes.push(PolicyOutcomes { policy, outcomes }),
+                Err(Cancelled) => break,
+            }
+        }
+
+        if policy_outcomes.len() == POLICIES.len() {
+            transport.send(Response::PolicyOutcomes(Tagged {
+                generation,
+                value: policy_outcomes,
+            }));
+            transport.send(Response::Status(Status::Solved {
+                num_states,
+                simulating: false,
+            }));
+        } else {
+            self.policies_stale = true;
+        }
+        for request in deferred {
+            self.apply(request);
+        }
+    }
+}
+
+/// An adaptive simulation so far.
+struct AdaptiveRun {
+    tally: SimTally,
+    rng: ChaCha8Rng,
+    // Seconds spent on it, and when its results were last sent.
+    elapsed: f64,
+    last_published: f64,
+}
+
+impl AdaptiveRun {
+    fn new(seed: u64) -> Self {
+        Self {
+            tally: SimTally::default(),
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            elapsed: 0.0,
+            last_published: 0.0,
+        }
+    }
+}
+
+/// Adds runs from `start` to `run` until every result has a 95% confidence
+/// interval narrower than `width`, or `seconds` run out.
+///
+/// Calls `report` between batches of runs with the fraction of the time
+/// used, and every so often with the results so far. Stops early if `report`
+/// returns false; `run` can then be carried on with.
+fn simulate_adaptive(
+    solution: &Solution,
+    start: &GameState,
+    width: f64,
+    seconds: u32,
+    run: &mut AdaptiveRun,
+    mut report: impl FnMut(f64, Option<&[SimResult]>) -> bool,
+) -> Result<Vec<SimResult>, Cancelled> {
+    let stopwatch = Stopwatch::start();
+    let elapsed_before = run.elapsed;
+    loop {
+        solution.simulate_more(&mut run.tally, ADAPTIVE_BATCH, start, &mut run.rng);
+        let outcomes = solution.outcomes_of(&run.tally);
+        run.elapsed = elapsed_before + stopwatch.elapsed_secs();
+        let precise = outcomes
+            .iter()
+            .all(|r| r.interval.is_some_and(|(low, high)| high - low < width));
+        if precise
+            || run.elapsed >= f64::from(seconds)
+            || run.tally.runs() > u32::MAX - ADAPTIVE_BATCH
+        {
+            return Ok(outcomes);
+        }
+
+        let elapsed = run.elapsed;
+        let publish = elapsed - run.last_published >= PUBLISH_EVERY;
+        if publish {
+            run.last_published = elapsed;
+        }
+        let so_far = Some(&outcomes[..]).filter(|_| publish);
+        if !report(elapsed / f64::from(seconds), so_far) {
+            return Err(Cancelled);
+        }
+    }
+}
+
+/// Measures how long work takes; `Instant` isn't available on the web.
+struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: std::time::Instant,
+    #[cfg(target_arch = "wasm32")]
+    start_ms: f64,
+}
+
+impl Stopwatch {
+    fn start() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            start: std::time::Instant::now(),
+            #[cfg(target_arch = "wasm32")]
+            start_ms: js_sys::Date::now(),
+        }
+    }
+
+    fn elapsed_secs(&self) -> f64 {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.start.elapsed().as_secs_f64();
+        #[cfg(target_arch = "wasm32")]
+        return (js_sys::Date::now() - self.start_ms) / 1000.0;
+    }
+}
+
+/// Sends `fraction` as progress if it's moved enough since `last_sent`.
+fn send_progress(transport: &mut impl Transport, last_sent: &mut Option<f32>, fraction: f64) {
+    let fraction = fraction as f32;
+    if last_sent.is_some_and(|last| (fraction - last).abs() < PROGRESS_STEP) {
+        return;
+    }
+    *last_sent = Some(fraction);
+    transport.send(Response::Progress(fraction));
+}
+
+/// The UI's picture of the engine, kept up to date from its responses.
+#[derive(Default)]
+pub(super) struct Results {
+    status: Status,
+    progress: Option<f32>,
+    // The last request sent to the engine.
+    generation: u32,
+    // Kept after newer requests go out, so there's something to show until
+    // the engine catches up.
+    choices: Option<Tagged<Option<ArrayVec<Answer, 3>>>>,
+    outcomes: Option<Tagged<Vec<SimResult>>>,
+    choice_outcomes: Option<Tagged<Vec<ChoiceOutcomes>>>,
+    policy_outcomes: Option<Tagged<Vec<PolicyOutcomes>>>,
+    // Why the engine died, until the handle notices.
+    crash: Option<String>,
+}
+
+impl Results {
+    pub(super) fn receive(&mut self, response: Response) {
+        match response {
+            Response::Status(status) => {
+                self.status = status;
+                self.progress = None;
+            }
+            Response::Progress(progress) => self.progress = Some(progress),
+            Response::Choices(choices) => keep_newest(&mut self.choices, choices),
+            Response::SimResults(outcomes) => keep_newest(&mut self.outcomes, outcomes),
+            Response::ChoiceOutcomes(outcomes) => keep_newest(&mut self.choice_outcomes, outcomes),
+            Response::PolicyOutcomes(outcomes) => keep_newest(&mut self.policy_outcomes, outcomes),
+        }
+    }
+
+    /// Records that the engine died, with whatever it had to say about it.
+    pub(super) fn crashed(&mut self, message: String) {
+        self.crash = Some(message);
+    }
+
+    /// Numbers the next request.
+    fn next_generation(&mut self) -> u32 {
+        self.generation += 1;
+        self.generation
+    }
+
+    fn publish<T: Clone>(&self, result: &Tagged<T>) -> Published<T> {
+        Published {
+            value: result.value.clone(),
+            current: result.generation == self.generation,
+        }
+    }
+}
+
+/// Puts `result` in `slot` unless what's already there answers a newer
+/// request.
+fn keep_newest<T>(slot: &mut Option<Tagged<T>>, result: Tagged<T>) {
+    if slot
+        .as_ref()
+        .is_none_or(|old| old.generation <= result.generation)
+    {
+        *slot = Some(result);
+    }
+}
+
+/// Everything the engine has been asked to solve for.
+#[derive(Clone)]
+struct Inputs {
+    scoring: Option<Scoring>,
+    risk: Risk,
+    rates: SuccessRates,
+    sim_method: Option<SimMethod>,
+    game_state: GameState,
+    compare_policies: bool,
+}
+
+/// Why the engine last died.
+pub(super) struct Crash {
+    pub(super) message: String,
+    /// Whether a new engine took over. One that dies on the same inputs as
+    /// the last isn't replaced until the inputs change.
+    pub(super) restarted: bool,
+    // The last request sent when the engine died or was restarted.
+    generation: u32,
+}
+
+/// The UI's end of the engine.
+pub(super) struct ThreadHandle {
+    client: Client,
+    repaint_signal: Arc<dyn RepaintSignal>,
+    inputs: Inputs,
+    crash: Option<Crash>,
+}
+
+impl ThreadHandle {
+    pub(super) fn spawn(
+        scoring: Option<Scoring>,
+        risk: Risk,
+        rates: SuccessRates,
+        game_state: GameState,
+        sim_method: Option<SimMethod>,
+        compare_policies: bool,
+        repaint_signal: Arc<dyn RepaintSignal>,
+    ) -> Self {
+        let this = Self {
+            client: Client::spawn(Arc::clone(&repaint_signal)),
+            repaint_signal,
+            inputs: Inputs {
+                scoring,
+                risk,
+                rates,
+                sim_method,
+                game_state,
+                compare_policies,
+            },
+            crash: None,
+        };
+        this.send_inputs();
+        this
+    }
+
+    fn send_inputs(&self) {
+        let inputs = self.inputs.clone();
+        if let Some(scoring) = inputs.scoring {
+            self.post(Request::Weights(scoring));
+        }
+        self.post(Request::Risk(inputs.risk));
+        self.post(Request::Rates(inputs.rates));
+        if let Some(sim_method) = inputs.sim_method {
+            self.post(Request::SimMethod(sim_method));
+        }
+        self.post(Request::GameState(inputs.game_state));
+        self.post(Request::ComparePolicies(inputs.compare_policies));
+    }
+
+    fn post(&self, request: Request) {
+        let generation = self.client.with_results(|results| {
+            if request.changes_results() {
+                results.next_generation()
+            } else {
+                // Results already in stay current.
+                results.generation
+            }
+        });
+        self.client.send(Tagged {
+            generation,
+            value: request,
+        });
+    }
+
+    fn send(&mut self, request: Request) {
+        // The user has moved on since the engine came back.
+        if self.crash.as_ref().is_some_and(|crash| crash.restarted) {
+            self.crash = None;
+        }
+        self.post(request);
+    }
+
+    /// Replaces the engine if it has died. Call once a frame.
+    pub(super) fn supervise(&mut self) {
+        let (message, generation) = self
+            .client
+            .with_results(|results| (results.crash.take(), results.generation));
+        match message {
+            Some(message) => {
+                let retry = self
+                    .crash
+                    .as_ref()
+                    .is_none_or(|crash| crash.generation != generation);
+                self.crash = Some(Crash {
+                    message,
+                    restarted: false,
+                    generation,
+                });
+                if retry {
+                    self.restart();
+                }
+            }
+            None => {
+                let inputs_changed = self
+                    .crash
+                    .as_ref()
+                    .is_some_and(|crash| !crash.restarted && crash.generation != generation);
+                if inputs_changed {
+                    self.restart();
+                }
+            }
+        }
+    }
+
+    fn restart(&mut self) {
+        // Keep showing what the old engine worked out until the new one
+        // catches up.
+        let results = self.client.with_results(std::mem::take);
+        self.client = Client::spawn(Arc::clone(&self.repaint_signal));
+        self.client.with_results(|new| *new = results);
+        self.send_inputs();
+        let generation = self.client.with_results(|results| results.generation);
+        if let Some(crash) = &mut self.crash {
+            crash.restarted = true;
+            crash.generation = generation;
+        }
+    }
+
+    /// Why the engine last died, if the user hasn't moved on since.
+    pub(super) fn crash(&self) -> Option<&Crash> {
+        self.crash.as_ref()
+    }
+
+    pub(super) fn status(&self) -> Status {
+        self.client.with_results(|results| results.status)
+    }
+
+    /// Fraction of the current build or simulation done so far, if one is
+    /// running.
+    pub(super) fn progress(&self) -> Option<f32> {
+        self.client.with_results(|results| results.progress)
+    }
+
+    pub(super) fn update_weights(&mut self, scoring: Scoring) {
+        self.inputs.scoring = Some(scoring);
+        self.send(Request::Weights(scoring));
+    }
+
+    pub(super) fn update_risk(&mut self, risk: Risk) {
+        self.inputs.risk = risk;
+        self.send(Request::Risk(risk));
+    }
+
+    pub(super) fn update_rates(&mut self, rates: SuccessRates) {
+        self.inputs.rates = rates.clone();
+        self.send(Request::Rates(rates));
+    }
+
+    pub(super) fn update_sim_method(&mut self, sim_method: SimMethod) {
+        self.inputs.sim_method = Some(sim_method);
+        self.send(Request::SimMethod(sim_method));
+    }
+
+    pub(super) fn update_game_state(&mut self, game_state: GameState) {
+        self.inputs.game_state = game_state.clone();
+        self.send(Request::GameState(game_state));
+    }
+
+    /// Sets whether to compare the solver with the other policies. Call once
+    /// a frame; only changes are sent.
+    pub(super) fn update_compare_policies(&mut self, compare: bool) {
+        if compare != self.inputs.compare_policies {
+            self.inputs.compare_policies = compare;
+            self.send(Request::ComparePolicies(compare));
+        }
+    }
+
+    pub(super) fn sim_results(&self) -> Option<Published<Vec<SimResult>>> {
+        self.client.with_results(|results| {
+            let outcomes = results.outcomes.as_ref()?;
+            Some(results.publish(outcomes))
+        })
+    }
+
+    pub(super) fn choice_outcomes(&self) -> Option<Published<Vec<ChoiceOutcomes>>> {
+        self.client.with_results(|results| {
+            let outcomes = results.choice_outcomes.as_ref()?;
+            Some(results.publish(outcomes))
+        })
+    }
+
+    pub(super) fn policy_outcomes(&self) -> Option<Published<Vec<PolicyOutcomes>>> {
+        self.client.with_results(|results| {
+            let outcomes = results.policy_outcomes.as_ref()?;
+            Some(results.publish(outcomes))
+        })
+    }
+
+    /// The choices from the latest game state sent, best first; `None` once
+    /// the stone is finished.
+    pub(super) fn sorted_choices(&self) -> Option<Published<ArrayVec<Answer, 3>>> {
+        self.client.with_results(|results| {
+            let choices = results.publish(results.choices.as_ref()?);
+            Some(Published {
+                value: choices.value?,
+                current: choices.current,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::{Chance, ChanceLadder, Goal, TABLE_LEN};
+    use std::collections::VecDeque;
+
+    /// A transport on the test's own thread: it hands out queued requests
+    /// and keeps every response.
+    #[derive(Default)]
+    struct Script {
+        requests: VecDeque<Tagged<Request>>,
+        // Queued along with the first progress report, so they arrive in
+        // the middle of the work.
+        interrupt: Vec<Tagged<Request>>,
+        responses: Vec<Response>,
+    }
+
+    impl Script {
+        fn push(&mut self, generation: u32, value: Request) {
+            self.requests.push_back(Tagged { generation, value });
+        }
+
+        /// Generation of every result sent, in order.
+        fn generations(&self) -> Vec<u32> {
+            self.responses
+                .iter()
+                .filter_map(|response| match response {
+                    Response::Status(_) | Response::Progress(_) => None,
+                    Response::Choices(r) => Some(r.generation),
+                    Response::SimResults(r) => Some(r.generation),
+                    Response::ChoiceOutcomes(r) => Some(r.generation),
+                    Response::PolicyOutcomes(r) => Some(r.generation),
+                })
+                .collect()
+        }
+
+        fn builds_started(&self) -> usize {
+            self.responses
+                .iter()
+                .filter(|response| matches!(response, Response::Status(Status::Solving)))
+                .count()
+        }
+    }
+
+    impl Transport for Script {
+        fn recv(&mut self) -> Option<Tagged<Request>> {
+            self.requests.pop_front()
+        }
+
+        fn try_recv(&mut self) -> Option<Tagged<Request>> {
+            self.requests.pop_front()
+        }
+
+        fn send(&mut self, response: Response) {
+            if let Response::Progress(_) = response {
+                self.requests.extend(self.interrupt.drain(..));
+            }
+            self.responses.push(response);
+        }
+    }
+
+    fn weights() -> Request {
+        Request::Weights(PRESETS[0].scoring)
+    }
+
+    fn stone(num_slots: u8) -> GameState {
+        GameState::new(num_slots, ChanceLadder::default())
+    }
+
+    #[test]
+    fn results_answer_the_last_request_applied() {
+        let mut script = Script::default();
+        script.push(1, weights());
+        script.push(2, Request::SimMethod(SimMethod::Exact));
+        script.push(3, Request::GameState(stone(5)));
+        Engine::new(false).run(&mut script);
+
+        let generations = script.generations();
+        assert!(!generations.is_empty());
+        assert!(generations.iter().all(|&generation| generation == 3));
+        assert!(script
+            .responses
+            .iter()
+            .any(|response| matches!(response, Response::SimResults(_))));
+    }
+
+    #[test]
+    fn a_new_stone_cancels_the_build() {
+        let mut script = Script::default();
+        script.push(1, weights());
+        script.interrupt.push(Tagged {
+            generation: 2,
+            value: Request::GameState(stone(6)),
+        });
+        Engine::new(false).run(&mut script);
+
+        assert_eq!(script.builds_started(), 2);
+        let solution = Solution::build(
+            PRESETS[0].scoring,
+            Risk::Neutral,
+            ChanceLadder::default(),
+            &SuccessRates::advertised(),
+            6,
+        )
+        .unwrap();
+        for response in &script.responses {
+            if let Response::Status(Status::Solved { num_states, .. }) = response {
+                assert_eq!(*num_states, solution.num_states());
+            }
+        }
+        assert!(script
+            .generations()
+            .iter()
+            .all(|&generation| generation == 2));
+    }
+
+    #[test]
+    fn clicks_wait_for_the_build() {
+        let mut clicked = GameState::default();
+        clicked.record(0, true);
+        let mut script = Script::default();
+        script.push(1, weights());
+        script.interrupt.push(Tagged {
+            generation: 2,
+            value: Request::GameState(clicked),
+        });
+        Engine::new(false).run(&mut script);
+
+        // The click doesn't change the solution, but it does make anything
+        // worked out for the fresh stone out of date.
+        assert_eq!(script.builds_started(), 1);
+        assert!(!script.generations().is_empty());
+        assert!(script
+            .generations()
+            .iter()
+            .all(|&generation| generation == 2));
+    }
+
+    #[test]
+    fn going_back_reuses_the_cached_solution() {
+        let mut engine = Engine::new(false);
+        let mut script = Script::default();
+        script.push(1, weights());
+        engine.catch_up(&mut script);
+        script.push(2, Request::GameState(stone(6)));
+        engine.catch_up(&mut script);
+        assert_eq!(script.builds_started(), 2);
+
+        script.push(3, Request::GameState(GameState::default()));
+        engine.catch_up(&mut script);
+        assert_eq!(script.builds_started(), 2);
+        assert_eq!(script.generations().last(), Some(&3));
+    }
+
+    #[test]
+    fn policies_are_compared_only_when_asked_and_last() {
+        let mut engine = Engine::new(false);
+        let mut script = Script::default();
+        script.push(1, weights());
+        script.push(2, Request::SimMethod(SimMethod::Exact));
+        script.push(3, Request::GameState(stone(5)));
+        engine.catch_up(&mut script);
+        let is_policies = |response: &Response| matches!(response, Response::PolicyOutcomes(_));
+        assert!(!script.responses.iter().any(is_policies));
+
+        // Asking doesn't make what's already there out of date.
+        script.push(3, Request::ComparePolicies(true));
+        let before = script.responses.len();
+        engine.catch_up(&mut script);
+        assert_eq!(script.builds_started(), 1);
+        let policies = script.responses[before..]
+            .iter()
+            .filter(|response| is_policies(response))
+            .count();
+        assert_eq!(policies, 1);
+        assert!(script
+            .generations()
+            .iter()
+            .all(|&generation| generation == 3));
+
+        // With the comparison on, it follows every other result.
+        script.push(4, Request::GameState(stone(6)));
+        engine.catch_up(&mut script);
+        let last_result = script
+            .responses
+            .iter()
+            .rposition(|response| !matches!(response, Response::Status(_) | Response::Progress(_)));
+        assert!(is_policies(&script.responses[last_result.unwrap()]));
+        assert_eq!(script.generations().last(), Some(&4));
+    }
+
+    #[test]
+    fn sliced_simulations_carry_on_until_out_of_date() {
+        let mut engine = Engine::new(false);
+        engine.slice = Some(0.0);
+        let runs = |engine: &Engine| engine.paused.as_ref().map(|run| run.tally.runs());
+        let mut script = Script::default();
+        script.push(1, weights());
+        script.push(
+            2,
+            Request::SimMethod(SimMethod::Adaptive {
+                width: 0.001,
+                seconds: 60,
+                seed: 0,
+            }),
+        );
+        assert!(engine.catch_up(&mut script));
+        assert_eq!(runs(&engine), Some(ADAPTIVE_BATCH));
+        assert!(engine.catch_up(&mut script));
+        assert_eq!(runs(&engine), Some(2 * ADAPTIVE_BATCH));
+
+        // A new stone starts over.
+        script.push(3, Request::GameState(stone(5)));
+        assert!(engine.catch_up(&mut script));
+        assert_eq!(runs(&engine), Some(ADAPTIVE_BATCH));
+
+        script.push(
+            4,
+            Request::SimMethod(SimMethod::Adaptive {
+                width: 0.5,
+                seconds: 60,
+                seed: 0,
+            }),
+        );
+        assert!(!engine.catch_up(&mut script));
+        assert_eq!(runs(&engine), None);
+        assert!(matches!(
+            script
+                .responses
+                .iter()
+                .rev()
+                .find(|r| matches!(r, Response::SimResults(_))),
+            Some(Response::SimResults(Tagged { generation: 4, .. }))
+        ));
+    }
+
+    #[test]
+    fn late_results_are_kept_until_newer_ones_arrive() {
+        let mut results = Results::default();
+        let first = results.next_generation();
+        let second = results.next_generation();
+        let answer = |generation, score| {
+            Response::SimResults(Tagged {
+                generation,
+                value: vec![SimResult {
+                    counts: [0; 3],
+                    probability: 1.0,
+                    interval: None,
+                    score,
+                }],
+            })
+        };
+
+        results.receive(answer(second, 2.0));
+        let shown = results.publish(results.outcomes.as_ref().unwrap());
+        assert!(shown.current);
+        assert_eq!(shown.value[0].score, 2.0);
+
+        // An answer to an older request doesn't replace a newer one.
+        results.receive(answer(first, 1.0));
+        let shown = results.publish(results.outcomes.as_ref().unwrap());
+        assert_eq!(shown.value[0].score, 2.0);
+
+        // Once another request goes out, what's shown is out of date.
+        results.next_generation();
+        assert!(!results.publish(results.outcomes.as_ref().unwrap()).current);
+    }
+
+    #[test]
+    fn messages_survive_the_trip_to_a_worker() {
+        let mut game_state = stone(10);
+        game_state.record(2, false);
+        game_state.set_chance(Chance::from_percent(35));
+        game_state.record(0, true);
+        let requests = vec![
+            Request::Weights(Scoring::Table {
+                payoff: [[0.5; TABLE_LEN], [-1.0; TABLE_LEN], [0.0; TABLE_LEN]],
+            }),
+            Request::Weights(Scoring::Goal(Goal {
+                min_skills: [7, 6],
+                max_negative: 4,
+            })),
+            Request::Risk(Risk::Cvar { alpha: 0.1 }),
+            Request::Rates(SuccessRates::advertised().with_rate(Chance::from_percent(25), 0.2)),
+            Request::SimMethod(SimMethod::Adaptive {
+                width: 0.01,
+                seconds: 30,
+                seed: u64::MAX - 1,
+            }),
+            Request::GameState(game_state),
+        ];
+        for (generation, value) in (1..).zip(requests) {
+            let request = Tagged { generation, value };
+            let decoded: Tagged<Request> = decode(&encode(&request)).unwrap();
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", request));
+        }
+
+        let outcomes = vec![
+            SimResult {
+                counts: [7, 6, 2],
+                probability: 0.25,
+                interval: Some((0.2, 0.3)),
+                score: 1.0,
+            },
+            SimResult {
+                counts: [0, 0, 0],
+                probability: 0.75,
+                interval: None,
+                score: f64::NEG_INFINITY,
+            },
+        ];
+        let responses = vec![
+            Response::Status(Status::Failed(BuildError::TooManySlots(17))),
+            Response::Progress(0.5),
+            Response::Choices(Tagged {
+                generation: 4,
+                value: Some(
+                    [
+                        Answer {
+                            index: 2,
+                            score: 0.5,
+                            mean: 0.5,
+                            std_dev: None,
+                        },
+                        Answer {
+                            index: 0,
+                            score: 0.25,
+                            mean: 0.5,
+                            std_dev: Some(0.1),
+                        },
+                    ]
+                    .iter()
+                    .copied()
+                    .collect(),
+                ),
+            }),
+            Response::SimResults(Tagged {
+                generation: 5,
+                value: outcomes.clone(),
+            }),
+            Response::ChoiceOutcomes(Tagged {
+                generation: 6,
+                value: vec![ChoiceOutcomes {
+                    index: 1,
+                    outcomes: outcomes.clone(),
+                }],
+            }),
+            Response::PolicyOutcomes(Tagged {
+                generation: 7,
+                value: POLICIES
+                    .iter()
+                    .map(|&policy| PolicyOutcomes {
+                        policy,
+                        outcomes: outcomes.clone(),
+                    })
+                    .collect(),
+            }),
+        ];
+        for response in responses {
+            let decoded: Response = decode(&encode(&response)).unwrap();
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", response));
+        }
+
+        assert!(decode::<Tagged<Request>>(&[0xff; 3]).is_none());
+    }
+}
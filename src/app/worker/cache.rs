@@ -0,0 +1,75 @@
+//! Solutions the engine has already built, so going back to one is instant.
+
+use crate::solver::{BuildError, ChanceLadder, Risk, Scoring, Solution, SuccessRates};
+use std::rc::Rc;
+
+// How many bytes of tables to keep across every cached solution.
+const MAX_BYTES: usize = 64 << 20;
+
+/// Everything a solution depends on.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Key {
+    pub(super) scoring: Scoring,
+    pub(super) risk: Risk,
+    pub(super) ladder: ChanceLadder,
+    pub(super) rates: SuccessRates,
+    pub(super) num_slots: u8,
+}
+
+impl Key {
+    pub(super) fn build(&self, progress: impl FnMut(f64) -> bool) -> Result<Solution, BuildError> {
+        Solution::build_with_progress(
+            self.scoring,
+            self.risk,
+            self.ladder,
+            &self.rates,
+            self.num_slots,
+            progress,
+        )
+    }
+
+    /// Bytes the solution for this key will take once it's built.
+    pub(super) fn memory_needed(&self) -> usize {
+        Solution::memory_needed(self.scoring, self.risk, self.ladder, self.num_slots)
+    }
+}
+
+/// The most recently used solutions.
+#[derive(Default)]
+pub(super) struct SolutionCache {
+    // Least recently used first.
+    entries: Vec<(Key, Rc<Solution>)>,
+    bytes: usize,
+}
+
+impl SolutionCache {
+    pub(super) fn contains(&self, key: &Key) -> bool {
+        self.entries.iter().any(|(k, _)| k == key)
+    }
+
+    /// The solution for `key`, if it's cached, which then counts as the most
+    /// recently used.
+    pub(super) fn get(&mut self, key: &Key) -> Option<Rc<Solution>> {
+        let i = self.entries.iter().position(|(k, _)| k == key)?;
+        let entry = self.entries.remove(i);
+        let solution = Rc::clone(&entry.1);
+        self.entries.push(entry);
+        Some(solution)
+    }
+
+    /// Whether a solution of `bytes` fits without dropping anything.
+    pub(super) fn has_room_for(&self, bytes: usize) -> bool {
+        self.bytes + bytes <= MAX_BYTES
+    }
+
+    /// Adds `solution` as the most recently used, dropping the least recently
+    /// used ones to make room. The newest always stays, however big.
+    pub(super) fn insert(&mut self, key: Key, solution: Rc<Solution>) {
+        self.bytes += solution.memory_size();
+        self.entries.push((key, solution));
+        while self.bytes > MAX_BYTES && self.entries.len() > 1 {
+            let (_, oldest) = self.entries.remove(0);
+            self.bytes -= oldest.memory_size();
+        }
+    }
+}
@@ -0,0 +1,464 @@
+#![forbid(unsafe_code)]
+#![cfg_attr(not(debug_assertions), deny(warnings))] // Forbid warnings in release builds
+#![warn(clippy::all, rust_2018_idioms)]
+
+use lost_ark_refiner::solver::{
+    read_presets, Chance, ChanceLadder, GameState, Goal, Policy, Preset, Risk, Scoring, Solution,
+    SuccessRates, MAX_SLOTS, POLICIES, PRESETS,
+};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::{env, fs, process};
+
+const USAGE: &str = "\
+Usage: refiner [OPTIONS]
+
+Prints the best row to click next on an ability stone, the score of each
+choice, and the most likely final results when playing optimally.
+
+Stone:
+  --slots N               slots per row (default 8)
+  --skill1 RESULTS        clicks so far in skill 1, e.g. 1101 (1 or + for a
+  --skill2 RESULTS          success, 0 or - for a failure)
+  --negative RESULTS
+  --chance PERCENT        current success chance; required once any clicks
+                          have been recorded
+  --ladder MIN,MAX,START,SUCCESS,FAIL
+                          success chance rules in percent (default
+                          25,75,75,-10,10)
+  --rate CHANCE=PERCENT   assume clicks at CHANCE% really succeed PERCENT% of
+                          the time; may be repeated
+
+Scoring (default: the first preset):
+  --preset NAME|NUMBER    one of the presets listed by --list-presets
+  --presets FILE          add the presets in a preset pack exported from the
+                          app; may be repeated
+  --success S1,S2,NEG     points for a success in each row
+  --fail S1,S2,NEG        points for a failure in each row (default 0,0,0)
+  --goal S1,S2,NEG        maximize the chance of reaching at least S1 and S2
+                          successes with at most NEG negative successes
+
+Risk (default: maximize the average):
+  --stddev LAMBDA         maximize average - LAMBDA standard deviations
+  --cvar PERCENT          maximize the average of the worst PERCENT% of results
+
+Output:
+  --top N                 number of final results to list, 0 for all
+                          (default 10)
+  --compare               also compare the solver with common rules of thumb
+                          and random clicking, including how often each
+                          reaches the goal (7,7,4 unless --goal is given)
+  --list-presets          print the presets and exit
+  -h, --help              print this message and exit
+";
+
+const ROW_LABELS: [&str; 3] = ["Skill 1", "Skill 2", "Negative"];
+
+// The goal --compare checks policies against without --goal.
+const DEFAULT_GOAL: [u8; 3] = [7, 7, 4];
+
+// Runs to estimate how random clicking does from, and the seed they're
+// drawn with.
+const RANDOM_RUNS: u32 = 100_000;
+const RANDOM_SEED: u64 = 0;
+
+struct Args {
+    num_slots: u8,
+    rows: [Vec<bool>; 3],
+    chance: Option<u8>,
+    ladder: ChanceLadder,
+    rates: SuccessRates,
+    presets: Vec<Preset>,
+    preset: Option<String>,
+    success: Option<[f64; 3]>,
+    fail: Option<[f64; 3]>,
+    goal: Option<[u8; 3]>,
+    risk: Risk,
+    top: usize,
+    compare: bool,
+}
+
+fn main() {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => return,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(err) = run(&args) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut out = Args {
+        num_slots: 8,
+        rows: Default::default(),
+        chance: None,
+        ladder: ChanceLadder::default(),
+        rates: SuccessRates::advertised(),
+        presets: PRESETS.to_vec(),
+        preset: None,
+        success: None,
+        fail: None,
+        goal: None,
+        risk: Risk::Neutral,
+        top: 10,
+        compare: false,
+    };
+
+    let mut list_presets = false;
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", flag));
+        match flag.as_str() {
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                return Ok(None);
+            }
+            "--list-presets" => list_presets = true,
+            "--slots" => out.num_slots = parse_number(&flag, &value()?)?,
+            "--skill1" => out.rows[0] = parse_row(&flag, &value()?)?,
+            "--skill2" => out.rows[1] = parse_row(&flag, &value()?)?,
+            "--negative" => out.rows[2] = parse_row(&flag, &value()?)?,
+            "--chance" => out.chance = Some(parse_number(&flag, value()?.trim_end_matches('%'))?),
+            "--ladder" => {
+                let value = value()?;
+                let parts = value.split(',').collect::<Vec<_>>();
+                if parts.len() != 5 {
+                    return Err(format!("{} needs five comma-separated values", flag));
+                }
+                out.ladder = ChanceLadder {
+                    min: parse_number(&flag, parts[0])?,
+                    max: parse_number(&flag, parts[1])?,
+                    start: parse_number(&flag, parts[2])?,
+                    on_success: parse_number(&flag, parts[3])?,
+                    on_fail: parse_number(&flag, parts[4])?,
+                };
+            }
+            "--rate" => {
+                let value = value()?;
+                let (chance, rate) = value
+                    .split_once('=')
+                    .ok_or_else(|| format!("{} needs CHANCE=PERCENT", flag))?;
+                let chance: u8 = parse_number(&flag, chance.trim_end_matches('%'))?;
+                let rate: f64 = parse_number(&flag, rate.trim_end_matches('%'))?;
+                out.rates = std::mem::take(&mut out.rates)
+                    .with_rate(Chance::from_percent(chance), rate / 100.0);
+            }
+            "--preset" => out.preset = Some(value()?),
+            "--presets" => {
+                let path = value()?;
+                let text = fs::read_to_string(&path)
+                    .map_err(|e| format!("couldn't read {}: {}", path, e))?;
+                let presets = read_presets(&text).map_err(|e| format!("{}: {}", path, e))?;
+                out.presets.extend(presets);
+            }
+            "--success" => out.success = Some(parse_triple(&flag, &value()?)?),
+            "--fail" => out.fail = Some(parse_triple(&flag, &value()?)?),
+            "--goal" => out.goal = Some(parse_triple(&flag, &value()?)?),
+            "--stddev" => {
+                out.risk = Risk::MeanStdDev {
+                    lambda: parse_number(&flag, &value()?)?,
+                }
+            }
+            "--cvar" => {
+                let percent: u8 = parse_number(&flag, value()?.trim_end_matches('%'))?;
+                if !(1..=100).contains(&percent) {
+                    return Err(format!("{} must be between 1 and 100", flag));
+                }
+                out.risk = Risk::Cvar {
+                    alpha: f64::from(percent) / 100.0,
+                };
+            }
+            "--top" => out.top = parse_number(&flag, &value()?)?,
+            "--compare" => out.compare = true,
+            _ => return Err(format!("unknown option {}", flag)),
+        }
+    }
+
+    if list_presets {
+        for (i, preset) in out.presets.iter().enumerate() {
+            println!("{}: {}", i + 1, preset.name);
+        }
+        return Ok(None);
+    }
+
+    Ok(Some(out))
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", flag, value))
+}
+
+fn parse_triple<T: std::str::FromStr>(flag: &str, value: &str) -> Result<[T; 3], String> {
+    let mut parts = value.split(',');
+    let mut next = || match parts.next() {
+        Some(part) => parse_number(flag, part),
+        None => Err(format!("{} needs three comma-separated values", flag)),
+    };
+    let triple = [next()?, next()?, next()?];
+    if parts.next().is_some() {
+        return Err(format!("{} needs three comma-separated values", flag));
+    }
+    Ok(triple)
+}
+
+fn parse_row(flag: &str, value: &str) -> Result<Vec<bool>, String> {
+    value
+        .chars()
+        .map(|c| match c {
+            '1' | '+' => Ok(true),
+            '0' | '-' => Ok(false),
+            _ => Err(format!("invalid click for {}: {}", flag, c)),
+        })
+        .collect()
+}
+
+impl Args {
+    fn scoring(&self) -> Result<Scoring, String> {
+        let chosen = [
+            self.preset.is_some(),
+            self.success.is_some() || self.fail.is_some(),
+            self.goal.is_some(),
+        ];
+        if chosen.iter().filter(|&&c| c).count() > 1 {
+            return Err("use only one of --preset, --success/--fail and --goal".to_string());
+        }
+
+        if let Some(name) = &self.preset {
+            let preset = match name.parse::<usize>() {
+                Ok(n) => n.checked_sub(1).and_then(|i| self.presets.get(i)),
+                Err(_) => self
+                    .presets
+                    .iter()
+                    .find(|p| p.name.eq_ignore_ascii_case(name)),
+            };
+            return preset
+                .map(|p| p.scoring)
+                .ok_or_else(|| format!("unknown preset {}; see --list-presets", name));
+        }
+        if self.success.is_some() || self.fail.is_some() {
+            return Ok(Scoring::Weighted {
+                success: self.success.unwrap_or([0.0; 3]),
+                fail: self.fail.unwrap_or([0.0; 3]),
+            });
+        }
+        if self.goal.is_some() {
+            return Ok(Scoring::Goal(self.goal()));
+        }
+        Ok(PRESETS[0].scoring)
+    }
+
+    fn goal(&self) -> Goal {
+        let goal = self.goal.unwrap_or(DEFAULT_GOAL);
+        Goal {
+            min_skills: [goal[0], goal[1]],
+            max_negative: goal[2],
+        }
+    }
+
+    fn game_state(&self) -> Result<GameState, String> {
+        if self.num_slots == 0 || self.num_slots > MAX_SLOTS {
+            return Err(format!("--slots must be between 1 and {}", MAX_SLOTS));
+        }
+        let mut game_state = GameState::new(self.num_slots, self.ladder);
+        for (i, row) in self.rows.iter().enumerate() {
+            if row.len() > usize::from(self.num_slots) {
+                return Err(format!(
+                    "{} has more clicks than slots",
+                    ROW_LABELS[i].to_lowercase()
+                ));
+            }
+            for &success in row {
+                game_state.record(i, success);
+            }
+        }
+
+        // The order of clicks across rows isn't known, so the chance we end
+        // up with above is meaningless unless nothing has been clicked.
+        match self.chance {
+            Some(percent) => {
+                let chance = Chance::from_percent(percent);
+                if !self.ladder.levels().contains(&chance) {
+                    return Err(format!(
+                        "a {} chance can't come up with these rules",
+                        chance
+                    ));
+                }
+                game_state.set_chance(chance);
+                if !game_state.chance_is_reachable() {
+                    eprintln!(
+                        "warning: a {} chance can't come up after these results",
+                        chance
+                    );
+                }
+            }
+            None if self.rows.iter().any(|row| !row.is_empty()) => {
+                return Err("--chance is required once clicks have been recorded".to_string());
+            }
+            None => {}
+        }
+        Ok(game_state)
+    }
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    let scoring = args.scoring()?;
+    if scoring.is_probability() && args.risk != Risk::Neutral {
+        return Err("--stddev and --cvar don't apply to --goal".to_string());
+    }
+    let game_state = args.game_state()?;
+    let solution = Solution::build(
+        scoring,
+        args.risk,
+        *game_state.ladder(),
+        &args.rates,
+        game_state.num_slots(),
+    )
+    .map_err(|e| e.to_string())?;
+
+    println!("Success chance: {}", game_state.chance());
+    match solution.sorted_choices(&game_state) {
+        Some(choices) => {
+            println!("Recommended: {}", ROW_LABELS[choices[0].index]);
+            println!();
+            let mut sorted = choices.clone();
+            sorted.sort_unstable_by_key(|a| a.index);
+            for choice in sorted {
+                let score = if scoring.is_probability() {
+                    format!("{:.2}%", 100.0 * choice.score)
+                } else {
+                    format!("{:.3}", choice.score)
+                };
+                print!("{:<10}{:>10}", ROW_LABELS[choice.index], score);
+                if let Some(std_dev) = choice.std_dev {
+                    print!("  (average {:.3} \u{b1} {:.3})", choice.mean, std_dev);
+                }
+                if choice.index == choices[0].index {
+                    print!("  *** BEST ***");
+                }
+                println!();
+            }
+        }
+        None => println!("The stone is finished."),
+    }
+
+    let mut outcomes = solution
+        .outcome_distribution(&game_state)
+        .into_iter()
+        .collect::<Vec<_>>();
+    outcomes.sort_unstable_by(|(a, pa), (b, pb)| pb.partial_cmp(pa).unwrap().then(a.cmp(b)));
+    if args.top > 0 {
+        outcomes.truncate(args.top);
+    }
+
+    println!();
+    println!(
+        "{:<10}{:<10}{:<10}{:>12}{:>10}",
+        ROW_LABELS[0], ROW_LABELS[1], ROW_LABELS[2], "Probability", "Score"
+    );
+    for (counts, probability) in outcomes {
+        println!(
+            "{:<10}{:<10}{:<10}{:>11.3}%{:>10.3}",
+            counts[0],
+            counts[1],
+            counts[2],
+            100.0 * probability,
+            solution.eval_result(counts)
+        );
+    }
+
+    if args.compare {
+        compare_policies(
+            &solution,
+            &game_state,
+            scoring.is_probability(),
+            args.goal(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Prints the average final score of every policy in `POLICIES` from
+/// `game_state`, how far short of the solver each one falls, and how often
+/// each reaches `goal`.
+fn compare_policies(solution: &Solution, game_state: &GameState, is_probability: bool, goal: Goal) {
+    let format = |score: f64| {
+        if is_probability {
+            format!("{:.2}%", 100.0 * score)
+        } else {
+            format!("{:.3}", score)
+        }
+    };
+    println!();
+    println!(
+        "{:<20}{:>10}{:>10}{:>12}{:>10}",
+        "Policy", "Average", "Std dev", "vs. solver", "Goal"
+    );
+    let mut best = None;
+    for policy in POLICIES {
+        let outcomes: Vec<([u8; 3], f64)> = match policy {
+            // Random clicks can reach far too many states to follow every
+            // one.
+            Policy::Random => {
+                let tally = solution
+                    .simulate_policy_with_progress(
+                        RANDOM_RUNS,
+                        game_state,
+                        policy,
+                        &mut StdRng::seed_from_u64(RANDOM_SEED),
+                        |_| true,
+                    )
+                    .unwrap_or_else(|_| unreachable!());
+                solution
+                    .outcomes_of(&tally)
+                    .into_iter()
+                    .map(|result| (result.counts, result.probability))
+                    .collect()
+            }
+            _ => solution
+                .policy_outcome_distribution_with_progress(game_state, policy, |_| true)
+                .unwrap_or_else(|_| unreachable!())
+                .into_iter()
+                .collect(),
+        };
+        let mean = outcomes
+            .iter()
+            .map(|&(counts, p)| p * solution.eval_result(counts))
+            .sum::<f64>();
+        let variance = outcomes
+            .iter()
+            .map(|&(counts, p)| p * (solution.eval_result(counts) - mean).powi(2))
+            .sum::<f64>();
+        let reached = outcomes
+            .iter()
+            .filter(|&&(counts, _)| goal.is_met(counts))
+            .map(|&(_, p)| p)
+            .sum::<f64>();
+        let best = *best.get_or_insert(mean);
+        println!(
+            "{:<20}{:>10}{:>10}{:>12}{:>9.2}%",
+            policy.to_string(),
+            format(mean),
+            format(variance.sqrt()),
+            format(mean - best),
+            100.0 * reached
+        );
+    }
+    println!();
+    println!(
+        "Goal: at least +{} and +{} with at most +{} negative. {} is estimated from {} runs.",
+        goal.min_skills[0],
+        goal.min_skills[1],
+        goal.max_negative,
+        Policy::Random,
+        RANDOM_RUNS
+    );
+}
@@ -0,0 +1,60 @@
+//! The ability stone solver, usable without the GUI.
+//!
+//! Build a [`Solution`] once for a scoring and a stone size, then ask it for
+//! the best click from any [`GameState`]:
+//!
+//! ```
+//! use lost_ark_refiner::solver::{GameState, Risk, Scoring, Solution, SuccessRates};
+//!
+//! let scoring = Scoring::Weighted {
+//!     success: [1.0, 1.0, -1.0],
+//!     fail: [0.0, 0.0, 0.0],
+//! };
+//! let mut stone = GameState::default();
+//! let solution = Solution::build(
+//!     scoring,
+//!     Risk::Neutral,
+//!     *stone.ladder(),
+//!     &SuccessRates::advertised(),
+//!     stone.num_slots(),
+//! )
+//! .unwrap();
+//!
+//! let best = solution.sorted_choices(&stone).unwrap()[0];
+//! stone.record(best.index, true);
+//!
+//! let outcomes = solution.exact_top_10(&stone);
+//! assert!(outcomes[0].probability > 0.0);
+//! ```
+
+mod chance;
+mod game_state;
+mod policy;
+mod preset;
+mod rates;
+mod share;
+mod solution;
+
+pub use self::chance::{Chance, ChanceLadder};
+pub use self::game_state::{GameState, Move, MAX_SLOTS};
+pub use self::policy::{Policy, POLICIES};
+pub use self::preset::{read_presets, write_presets, Preset, PRESETS};
+pub use self::rates::{goodness_of_fit, BucketFit, SuccessRates};
+pub use self::share::SharedStone;
+pub use self::solution::{
+    Answer, BuildError, Cancelled, Goal, Risk, Scoring, SimTally, Solution, TABLE_LEN,
+};
+
+/// One possible final result of a stone and how likely it is.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy)]
+pub struct SimResult {
+    /// Number of successes in each row.
+    pub counts: [u8; 3],
+    pub probability: f64,
+    /// 95% confidence interval for `probability` if it was estimated from
+    /// simulated runs.
+    pub interval: Option<(f64, f64)>,
+    /// The result's final score under the solution's scoring.
+    pub score: f64,
+}
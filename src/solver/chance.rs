@@ -0,0 +1,167 @@
+use std::fmt;
+
+/// A success chance, in percent.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Chance(u8);
+
+impl Chance {
+    /// A chance of `percent`%, capped at 100%.
+    pub fn from_percent(percent: u8) -> Self {
+        Self(percent.min(100))
+    }
+
+    pub fn percent(self) -> u8 {
+        self.0
+    }
+
+    /// The chance as a probability between 0 and 1.
+    pub fn as_f64(self) -> f64 {
+        f64::from(self.0) / 100.0
+    }
+}
+
+impl fmt::Display for Chance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}%", self.0)
+    }
+}
+
+/// The rules for how the success chance moves as a stone is cut. All values
+/// are in percent.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChanceLadder {
+    pub min: u8,
+    pub max: u8,
+    pub start: u8,
+    pub on_success: i8,
+    pub on_fail: i8,
+}
+
+impl Default for ChanceLadder {
+    fn default() -> Self {
+        Self {
+            min: 25,
+            max: 75,
+            start: 75,
+            on_success: -10,
+            on_fail: 10,
+        }
+    }
+}
+
+impl ChanceLadder {
+    /// The chance on a fresh stone.
+    pub fn start(&self) -> Chance {
+        self.clamp(i16::from(self.start))
+    }
+
+    /// The chance after clicking at `chance`.
+    pub fn after(&self, chance: Chance, success: bool) -> Chance {
+        self.clamp(i16::from(chance.0) + i16::from(self.step(success)))
+    }
+
+    /// Every chance that can come up on a stone, in increasing order.
+    pub fn levels(&self) -> Vec<Chance> {
+        let mut levels = vec![self.start()];
+        let mut i = 0;
+        while i < levels.len() {
+            for success in [true, false] {
+                let next = self.after(levels[i], success);
+                if !levels.contains(&next) {
+                    levels.push(next);
+                }
+            }
+            i += 1;
+        }
+        levels.sort_unstable();
+        levels
+    }
+
+    /// Every chance that `successes` successful and `fails` failed clicks can
+    /// lead to from the starting chance, in any order, in increasing order.
+    pub fn reachable(&self, successes: usize, fails: usize) -> Vec<Chance> {
+        // Bit `p` of `after[s][f]` is set if `s` successes and `f` failures
+        // can lead to a `p`% chance.
+        let mut after = vec![vec![0u128; fails + 1]; successes + 1];
+        after[0][0] = 1 << self.start().0;
+        for s in 0..=successes {
+            for f in 0..=fails {
+                let mut reachable = after[s][f];
+                if s > 0 {
+                    reachable |= self.step_all(after[s - 1][f], true);
+                }
+                if f > 0 {
+                    reachable |= self.step_all(after[s][f - 1], false);
+                }
+                after[s][f] = reachable;
+            }
+        }
+        (0..=100)
+            .filter(|&p| after[successes][fails] & (1 << p) != 0)
+            .map(Chance)
+            .collect()
+    }
+
+    fn step_all(&self, chances: u128, success: bool) -> u128 {
+        let mut out = 0;
+        for p in 0..=100 {
+            if chances & (1 << p) != 0 {
+                out |= 1 << self.after(Chance(p), success).0;
+            }
+        }
+        out
+    }
+
+    fn step(&self, success: bool) -> i8 {
+        if success {
+            self.on_success
+        } else {
+            self.on_fail
+        }
+    }
+
+    fn clamp(&self, percent: i16) -> Chance {
+        let max = self.max.min(100);
+        let min = self.min.min(max);
+        Chance(percent.clamp(i16::from(min), i16::from(max)) as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn percents(chances: Vec<Chance>) -> Vec<u8> {
+        chances.into_iter().map(Chance::percent).collect()
+    }
+
+    #[test]
+    fn reachable_chances_stay_within_the_ladder() {
+        let ladder = ChanceLadder::default();
+        assert_eq!(percents(ladder.reachable(0, 0)), [75]);
+        // Every failure from the top is clamped.
+        assert_eq!(percents(ladder.reachable(0, 3)), [75]);
+        assert_eq!(percents(ladder.reachable(1, 1)), [65, 75]);
+        assert_eq!(percents(ladder.reachable(2, 1)), [55, 65]);
+        assert_eq!(percents(ladder.reachable(6, 0)), [25]);
+    }
+
+    #[test]
+    fn reachable_chances_follow_other_ladders() {
+        let ladder = ChanceLadder {
+            min: 30,
+            max: 70,
+            start: 50,
+            on_success: -20,
+            on_fail: 10,
+        };
+        assert_eq!(percents(ladder.levels()), [30, 40, 50, 60, 70]);
+        assert_eq!(percents(ladder.reachable(2, 0)), [30]);
+        assert_eq!(percents(ladder.reachable(1, 1)), [40]);
+        assert_eq!(percents(ladder.reachable(0, 3)), [70]);
+        // Whether the second success is clamped depends on the order.
+        assert_eq!(percents(ladder.reachable(2, 1)), [30, 40]);
+    }
+}
@@ -0,0 +1,381 @@
+use arrayvec::ArrayVec;
+
+use super::chance::{Chance, ChanceLadder};
+
+/// The most slots a row can have.
+pub const MAX_SLOTS: u8 = 16;
+
+type Row = ArrayVec<bool, { MAX_SLOTS as usize }>;
+
+/// One entry in a stone's history.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Move {
+    /// A click in `row` (0 and 1 are the skills, 2 is the negative).
+    Click { row: usize, success: bool },
+    /// The chance was set by hand, e.g. to match what the game shows.
+    SetChance(Chance),
+}
+
+/// A partially cut stone: everything that has happened to it so far, in
+/// order, along with the rows and success chance that history leads to.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(default))] // if we add new fields, give them default values when deserializing old state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameState {
+    num_slots: u8,
+    ladder: ChanceLadder,
+    history: Vec<Move>,
+    // Undone moves, most recently undone last.
+    undone: Vec<Move>,
+
+    // Derived from `history` by `replay`.
+    chance: Chance,
+    rows: [Row; 3],
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new(8, ChanceLadder::default())
+    }
+}
+
+impl GameState {
+    /// A fresh stone with `num_slots` slots per row.
+    ///
+    /// Panics if `num_slots` is more than `MAX_SLOTS`.
+    pub fn new(num_slots: u8, ladder: ChanceLadder) -> Self {
+        assert!(num_slots <= MAX_SLOTS, "too many slots: {}", num_slots);
+        Self {
+            num_slots,
+            ladder,
+            history: Vec::new(),
+            undone: Vec::new(),
+            chance: ladder.start(),
+            rows: [Row::new(), Row::new(), Row::new()],
+        }
+    }
+
+    pub fn chance(&self) -> Chance {
+        self.chance
+    }
+
+    pub fn num_slots(&self) -> u8 {
+        self.num_slots
+    }
+
+    /// Results of the clicks so far in row `i`; `true` is a success.
+    pub fn row(&self, i: usize) -> &[bool] {
+        &self.rows[i]
+    }
+
+    pub fn ladder(&self) -> &ChanceLadder {
+        &self.ladder
+    }
+
+    /// Every move so far, oldest first.
+    pub fn history(&self) -> &[Move] {
+        &self.history
+    }
+
+    /// Every click so far, oldest first, with the chance it was made at.
+    pub fn clicks(&self) -> Vec<(Chance, bool)> {
+        let mut chance = self.ladder.start();
+        let mut clicks = Vec::new();
+        for m in &self.history {
+            match *m {
+                Move::Click { success, .. } => {
+                    clicks.push((chance, success));
+                    chance = self.ladder.after(chance, success);
+                }
+                Move::SetChance(c) => chance = c,
+            }
+        }
+        clicks
+    }
+
+    /// Whether the recorded clicks, made in some order, could have led from
+    /// the starting chance to the current one. Only chances set by hand can
+    /// make this false.
+    pub fn chance_is_reachable(&self) -> bool {
+        let successes = self.rows.iter().flatten().filter(|&&s| s).count();
+        let fails = self.rows.iter().map(|r| r.len()).sum::<usize>() - successes;
+        self.ladder
+            .reachable(successes, fails)
+            .contains(&self.chance)
+    }
+
+    /// The chance the recorded clicks lead to in the order they were made,
+    /// ignoring any chances set by hand.
+    pub fn replayed_chance(&self) -> Chance {
+        let mut chance = self.ladder.start();
+        for m in &self.history {
+            if let Move::Click { success, .. } = *m {
+                chance = self.ladder.after(chance, success);
+            }
+        }
+        chance
+    }
+
+    /// Whether a solution built for `other` also applies to `self`.
+    pub fn same_rules(&self, other: &GameState) -> bool {
+        self.num_slots == other.num_slots && self.ladder == other.ladder
+    }
+
+    /// Overrides the current chance, e.g. to match what the game shows.
+    /// Chances that can't come up under the stone's rules are ignored, so
+    /// every state has a solution.
+    pub fn set_chance(&mut self, chance: Chance) {
+        if chance != self.chance && self.ladder.levels().contains(&chance) {
+            self.push(Move::SetChance(chance));
+        }
+    }
+
+    /// Changes the number of slots per row, dropping any recorded clicks
+    /// that no longer fit.
+    ///
+    /// Panics if `num_slots` is more than `MAX_SLOTS`.
+    pub fn set_num_slots(&mut self, num_slots: u8) {
+        assert!(num_slots <= MAX_SLOTS, "too many slots: {}", num_slots);
+        if num_slots == self.num_slots {
+            return;
+        }
+        self.num_slots = num_slots;
+        let mut row_lens = [0; 3];
+        self.history.retain(|m| match *m {
+            Move::Click { row, .. } => {
+                row_lens[row] += 1;
+                row_lens[row] <= num_slots
+            }
+            Move::SetChance(_) => true,
+        });
+        self.undone.clear();
+        self.replay();
+    }
+
+    /// Changes the chance rules. Chances set by hand that can't come up under
+    /// the new rules are forgotten.
+    pub fn set_ladder(&mut self, ladder: ChanceLadder) {
+        if ladder == self.ladder {
+            return;
+        }
+        self.ladder = ladder;
+        let levels = ladder.levels();
+        self.history.retain(|m| match m {
+            Move::Click { .. } => true,
+            Move::SetChance(chance) => levels.contains(chance),
+        });
+        self.undone.clear();
+        self.replay();
+    }
+
+    /// Records a click in `row` and moves the chance accordingly.
+    ///
+    /// Panics if the row is already full.
+    pub fn record(&mut self, row: usize, success: bool) {
+        assert!(
+            self.rows[row].len() < usize::from(self.num_slots),
+            "row {} is full",
+            row
+        );
+        self.push(Move::Click { row, success });
+    }
+
+    /// Forgets the last click recorded in `row`, wherever it falls in the
+    /// history, and returns whether it was a success.
+    pub fn remove_last_click(&mut self, row: usize) -> Option<bool> {
+        let pos = self
+            .history
+            .iter()
+            .rposition(|m| matches!(*m, Move::Click { row: r, .. } if r == row))?;
+        let success = match self.history.remove(pos) {
+            Move::Click { success, .. } => success,
+            Move::SetChance(_) => unreachable!(),
+        };
+        self.undone.clear();
+        self.replay();
+        Some(success)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    /// Takes back the most recent move.
+    pub fn undo(&mut self) {
+        if let Some(m) = self.history.pop() {
+            self.undone.push(m);
+            self.replay();
+        }
+    }
+
+    /// Puts back the most recently undone move.
+    pub fn redo(&mut self) {
+        if let Some(m) = self.undone.pop() {
+            self.history.push(m);
+            self.replay();
+        }
+    }
+
+    /// Clears every row and goes back to the starting chance.
+    pub fn reset(&mut self) {
+        self.history.clear();
+        self.undone.clear();
+        self.replay();
+    }
+
+    fn push(&mut self, m: Move) {
+        self.history.push(m);
+        self.undone.clear();
+        self.replay();
+    }
+
+    fn replay(&mut self) {
+        self.chance = self.ladder.start();
+        for row in &mut self.rows {
+            row.clear();
+        }
+        for m in &self.history {
+            match *m {
+                Move::Click { row, success } => {
+                    self.rows[row].push(success);
+                    self.chance = self.ladder.after(self.chance, success);
+                }
+                Move::SetChance(chance) => self.chance = chance,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn percent(stone: &GameState) -> u8 {
+        stone.chance().percent()
+    }
+
+    #[test]
+    fn undo_and_redo_step_through_moves_in_order() {
+        let mut stone = GameState::default();
+        stone.record(0, true);
+        stone.record(2, false);
+        stone.set_chance(Chance::from_percent(45));
+        stone.record(1, true);
+        assert_eq!(percent(&stone), 35);
+
+        stone.undo();
+        assert_eq!(percent(&stone), 45);
+        assert!(stone.row(1).is_empty());
+        stone.undo();
+        assert_eq!(percent(&stone), 75);
+        stone.undo();
+        assert_eq!(percent(&stone), 65);
+        assert!(stone.row(2).is_empty());
+
+        stone.redo();
+        assert_eq!(stone.row(2), [false]);
+        assert_eq!(percent(&stone), 75);
+        stone.redo();
+        assert_eq!(percent(&stone), 45);
+
+        // A new move forgets what was undone.
+        stone.record(1, false);
+        assert!(!stone.can_redo());
+        assert_eq!(percent(&stone), 55);
+    }
+
+    #[test]
+    fn removing_an_earlier_click_replays_the_rest() {
+        let mut stone = GameState::default();
+        stone.record(0, true);
+        stone.record(1, false);
+        stone.record(1, false);
+        assert_eq!(percent(&stone), 75);
+
+        // Without the success in row 0, both failures are clamped at 75%.
+        assert_eq!(stone.remove_last_click(0), Some(true));
+        assert!(stone.row(0).is_empty());
+        assert_eq!(stone.row(1), [false, false]);
+        assert_eq!(percent(&stone), 75);
+        assert_eq!(stone.remove_last_click(0), None);
+
+        stone.record(2, true);
+        stone.record(0, true);
+        assert_eq!(percent(&stone), 55);
+        assert_eq!(stone.remove_last_click(2), Some(true));
+        assert_eq!(percent(&stone), 65);
+    }
+
+    #[test]
+    fn fewer_slots_drop_the_clicks_that_no_longer_fit() {
+        let mut stone = GameState::new(3, ChanceLadder::default());
+        stone.record(0, true);
+        stone.record(0, true);
+        stone.record(0, false);
+        stone.record(2, false);
+        stone.record(1, true);
+        stone.undo();
+
+        stone.set_num_slots(2);
+        assert_eq!(stone.row(0), [true, true]);
+        assert_eq!(stone.row(2), [false]);
+        assert_eq!(percent(&stone), 65);
+        assert!(!stone.can_redo());
+    }
+
+    #[test]
+    fn new_rules_forget_chances_they_cant_produce() {
+        let mut stone = GameState::default();
+        stone.set_chance(Chance::from_percent(45));
+        stone.record(0, true);
+        assert_eq!(percent(&stone), 35);
+
+        stone.set_ladder(ChanceLadder {
+            min: 35,
+            max: 75,
+            start: 75,
+            on_success: -20,
+            on_fail: 20,
+        });
+        assert_eq!(
+            stone.history(),
+            [Move::Click {
+                row: 0,
+                success: true
+            }]
+        );
+        assert_eq!(percent(&stone), 55);
+    }
+
+    #[test]
+    fn chances_set_by_hand_must_fit_the_clicks() {
+        let mut stone = GameState::default();
+        stone.record(0, true);
+        assert!(stone.chance_is_reachable());
+        stone.set_chance(Chance::from_percent(45));
+        assert!(!stone.chance_is_reachable());
+
+        // Clicked the other way round, a failure then a success end at 65%.
+        stone.undo();
+        stone.record(1, false);
+        assert_eq!(percent(&stone), 75);
+        stone.set_chance(Chance::from_percent(65));
+        assert!(stone.chance_is_reachable());
+    }
+
+    #[test]
+    fn ignores_chances_the_rules_cant_produce() {
+        let mut stone = GameState::default();
+        stone.set_chance(Chance::from_percent(50));
+        assert_eq!(stone.chance(), ChanceLadder::default().start());
+        assert!(stone.history().is_empty());
+
+        stone.set_chance(Chance::from_percent(45));
+        assert_eq!(stone.chance(), Chance::from_percent(45));
+    }
+}
@@ -0,0 +1,77 @@
+use arrayvec::ArrayVec;
+use std::fmt;
+
+use super::chance::Chance;
+
+/// A way of picking clicks, to see how it does next to a solution's best
+/// ones.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// The solution's best click.
+    Optimal,
+    /// A skill when the chance is at least `percent`, otherwise the
+    /// negative. Of the two skills, the one with more slots left; once the
+    /// rows it would pick are full, whatever is left.
+    Threshold { percent: u8 },
+    /// Any row that can still be clicked, each as likely as the others.
+    Random,
+}
+
+/// The solver's policy followed by common rules of thumb and a random
+/// baseline.
+pub const POLICIES: [Policy; 6] = [
+    Policy::Optimal,
+    Policy::Threshold { percent: 35 },
+    Policy::Threshold { percent: 45 },
+    Policy::Threshold { percent: 55 },
+    Policy::Threshold { percent: 0 },
+    Policy::Random,
+];
+
+impl Policy {
+    /// The rows a heuristic clicks at `chance` with `remaining` slots left
+    /// in each row, and how likely it is to click each one.
+    ///
+    /// Panics for [`Policy::Optimal`], which needs a solution.
+    pub(super) fn clicks(self, chance: Chance, remaining: [u8; 3]) -> ArrayVec<(usize, f64), 3> {
+        let mut clicks = ArrayVec::new();
+        match self {
+            Policy::Optimal => panic!("the optimal policy needs a solution"),
+            Policy::Threshold { percent } => {
+                // Ties go to skill 1.
+                let skill = [1, 0]
+                    .iter()
+                    .copied()
+                    .filter(|&i| remaining[i] > 0)
+                    .max_by_key(|&i| remaining[i]);
+                let negative = Some(2).filter(|_| remaining[2] > 0);
+                let row = if chance.percent() >= percent {
+                    skill.or(negative)
+                } else {
+                    negative.or(skill)
+                };
+                clicks.extend(row.map(|row| (row, 1.0)));
+            }
+            Policy::Random => {
+                let open = (0..3)
+                    .filter(|&i| remaining[i] > 0)
+                    .collect::<ArrayVec<usize, 3>>();
+                let p = 1.0 / open.len() as f64;
+                clicks.extend(open.into_iter().map(|row| (row, p)));
+            }
+        }
+        clicks
+    }
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Policy::Optimal => write!(f, "Solver"),
+            Policy::Threshold { percent: 0 } => write!(f, "Skills first"),
+            Policy::Threshold { percent } => write!(f, "Skills at \u{2265} {}%", percent),
+            Policy::Random => write!(f, "Random"),
+        }
+    }
+}
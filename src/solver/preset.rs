@@ -0,0 +1,153 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+
+use super::{
+    share::{parse_scoring, write_scoring},
+    Scoring,
+};
+
+/// A named scoring that covers a common goal.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preset {
+    pub name: Cow<'static, str>,
+    pub scoring: Scoring,
+}
+
+pub const PRESETS: [Preset; 6] = [
+    Preset {
+        name: Cow::Borrowed("Balanced; slightly prefer skill 1"),
+        scoring: Scoring::Weighted {
+            success: [1.1, 1.0, -1.0],
+            fail: [0.0, 0.0, 0.0],
+        },
+    },
+    Preset {
+        name: Cow::Borrowed("Balanced; slightly prefer skill 2"),
+        scoring: Scoring::Weighted {
+            success: [1.0, 1.1, -1.0],
+            fail: [0.0, 0.0, 0.0],
+        },
+    },
+    Preset {
+        name: Cow::Borrowed("Maximize skill 1"),
+        scoring: Scoring::Weighted {
+            success: [100.0, 1.0, -1.0],
+            fail: [0.0, 0.0, 0.0],
+        },
+    },
+    Preset {
+        name: Cow::Borrowed("Maximize skill 2"),
+        scoring: Scoring::Weighted {
+            success: [1.0, 100.0, -1.0],
+            fail: [0.0, 0.0, 0.0],
+        },
+    },
+    Preset {
+        name: Cow::Borrowed("Minimize negative; slightly prefer skill 1"),
+        scoring: Scoring::Weighted {
+            success: [1.1, 1.0, -100.0],
+            fail: [0.0, 0.0, 100.0],
+        },
+    },
+    Preset {
+        name: Cow::Borrowed("Minimize negative; slightly prefer skill 2"),
+        scoring: Scoring::Weighted {
+            success: [1.0, 1.1, -100.0],
+            fail: [0.0, 0.0, 100.0],
+        },
+    },
+];
+
+const PACK_HEADER: &str = "# Ability stone presets: one per line, the scoring then the name";
+
+/// Writes `presets` as a preset pack, a plain text file with one preset per
+/// line:
+///
+/// ```text
+/// w1.1,1,-1,0,0,0 Balanced; slightly prefer skill 1
+/// g7,7,4 77 stone
+/// ```
+///
+/// Scorings are written the same way as in a [`SharedStone`](super::SharedStone).
+pub fn write_presets(presets: &[Preset]) -> String {
+    let mut out = String::new();
+    out.push_str(PACK_HEADER);
+    out.push('\n');
+    for preset in presets {
+        // Writing to a `String` can't fail.
+        let _ = write_scoring(&mut out, &preset.scoring);
+        // Names can't span lines.
+        let name = preset.name.replace(&['\r', '\n'][..], " ");
+        let _ = writeln!(out, " {}", name.trim());
+    }
+    out
+}
+
+/// Reads a preset pack written by [`write_presets`]. Blank lines and lines
+/// starting with `#` are ignored.
+pub fn read_presets(text: &str) -> Result<Vec<Preset>, String> {
+    let mut presets = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (scoring, name) = line.split_once(' ').unwrap_or((line, ""));
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(format!("line {}: preset has no name", i + 1));
+        }
+        let scoring = parse_scoring(scoring).map_err(|e| format!("line {}: {}", i + 1, e))?;
+        presets.push(Preset {
+            name: Cow::Owned(name.to_string()),
+            scoring,
+        });
+    }
+    Ok(presets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::{Goal, TABLE_LEN};
+
+    #[test]
+    fn packs_round_trip() {
+        let mut payoff = [[0.0; TABLE_LEN]; 3];
+        payoff[1][9] = 3.25;
+        payoff[2][5] = -2.0;
+        let mut presets = PRESETS.to_vec();
+        presets.extend([
+            Preset {
+                name: Cow::Borrowed("77 stone"),
+                scoring: Scoring::Goal(Goal {
+                    min_skills: [7, 7],
+                    max_negative: 4,
+                }),
+            },
+            Preset {
+                name: Cow::Borrowed("Engraving table # with a hash"),
+                scoring: Scoring::Table { payoff },
+            },
+        ]);
+        assert_eq!(read_presets(&write_presets(&presets)), Ok(presets));
+    }
+
+    #[test]
+    fn names_are_written_on_one_line() {
+        let presets = [Preset {
+            name: Cow::Borrowed(" two\nlines "),
+            scoring: PRESETS[0].scoring,
+        }];
+        let read = read_presets(&write_presets(&presets)).unwrap();
+        assert_eq!(read[0].name, "two lines");
+    }
+
+    #[test]
+    fn rejects_presets_without_names() {
+        assert!(read_presets("w1,1,-1,0,0,0").is_err());
+        assert!(read_presets("w1,1,-1,0,0,0   ").is_err());
+        assert!(read_presets("w1,1,-1,0,0,0 ok\nw1,1,-1,0,0 short").is_err());
+    }
+}
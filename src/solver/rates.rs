@@ -0,0 +1,178 @@
+use super::chance::Chance;
+
+/// How often a click actually succeeds at each chance the game shows.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SuccessRates {
+    // Chances whose rate differs from what the game shows.
+    overrides: Vec<(Chance, f64)>,
+}
+
+impl SuccessRates {
+    /// Every chance succeeds exactly as often as the game says it does.
+    pub fn advertised() -> Self {
+        Self::default()
+    }
+
+    /// Success rates measured from logged clicks, for every chance with at
+    /// least `min_clicks` clicks; other chances keep their advertised rate.
+    pub fn observed(fits: &[BucketFit], min_clicks: u32) -> Self {
+        let mut rates = Self::default();
+        for fit in fits {
+            if fit.clicks >= min_clicks {
+                rates = rates.with_rate(fit.chance, fit.observed_rate());
+            }
+        }
+        rates
+    }
+
+    /// Overrides the rate at `chance` with `rate`, a probability between 0
+    /// and 1.
+    pub fn with_rate(mut self, chance: Chance, rate: f64) -> Self {
+        let rate = rate.clamp(0.0, 1.0);
+        self.overrides.retain(|&(c, _)| c != chance);
+        self.overrides.push((chance, rate));
+        self
+    }
+
+    /// Probability of a click succeeding at `chance`.
+    pub fn rate(&self, chance: Chance) -> f64 {
+        self.overrides
+            .iter()
+            .find(|&&(c, _)| c == chance)
+            .map_or_else(|| chance.as_f64(), |&(_, rate)| rate)
+    }
+
+    pub fn is_advertised(&self) -> bool {
+        self.overrides.is_empty()
+    }
+}
+
+/// How well the clicks logged at one chance match that chance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BucketFit {
+    pub chance: Chance,
+    pub clicks: u32,
+    pub successes: u32,
+    /// Probability of a result at least this far from the advertised rate
+    /// if the advertised rate is right (exact two-sided binomial test).
+    /// Small values suggest the advertised rate is wrong.
+    pub p_value: f64,
+}
+
+impl BucketFit {
+    pub fn observed_rate(&self) -> f64 {
+        f64::from(self.successes) / f64::from(self.clicks)
+    }
+}
+
+/// Groups logged `(chance, success)` clicks by chance and tests each group
+/// against its advertised rate. Results are in increasing order of chance.
+pub fn goodness_of_fit(clicks: impl IntoIterator<Item = (Chance, bool)>) -> Vec<BucketFit> {
+    let mut fits: Vec<BucketFit> = Vec::new();
+    for (chance, success) in clicks {
+        let fit = match fits.iter_mut().find(|f| f.chance == chance) {
+            Some(fit) => fit,
+            None => {
+                fits.push(BucketFit {
+                    chance,
+                    clicks: 0,
+                    successes: 0,
+                    p_value: 1.0,
+                });
+                fits.last_mut().unwrap()
+            }
+        };
+        fit.clicks += 1;
+        if success {
+            fit.successes += 1;
+        }
+    }
+    for fit in &mut fits {
+        fit.p_value = binomial_test(fit.clicks, fit.successes, fit.chance.as_f64());
+    }
+    fits.sort_unstable_by_key(|f| f.chance);
+    fits
+}
+
+/// Two-sided p-value of seeing `successes` out of `n` trials that each
+/// succeed with probability `p`: the total probability of every count no
+/// more likely than the one observed.
+fn binomial_test(n: u32, successes: u32, p: f64) -> f64 {
+    if p <= 0.0 {
+        return if successes == 0 { 1.0 } else { 0.0 };
+    }
+    if p >= 1.0 {
+        return if successes == n { 1.0 } else { 0.0 };
+    }
+
+    // Work with logs so large `n` doesn't underflow.
+    let mut log_pmf = Vec::with_capacity(n as usize + 1);
+    let mut log_p = f64::from(n) * (1.0 - p).ln();
+    let log_odds = (p / (1.0 - p)).ln();
+    for k in 0..=n {
+        log_pmf.push(log_p);
+        log_p += (f64::from(n - k) / f64::from(k + 1)).ln() + log_odds;
+    }
+
+    // Allow for rounding so counts exactly as likely as the observed one
+    // aren't left out.
+    let observed = log_pmf[successes as usize] + 1e-7;
+    let p_value = log_pmf
+        .iter()
+        .filter(|&&l| l <= observed)
+        .map(|l| l.exp())
+        .sum::<f64>();
+    p_value.min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn binomial_test_matches_known_p_values() {
+        // The most likely count, and every count is as likely or less.
+        assert!(close(binomial_test(10, 5, 0.5), 1.0));
+        // Only 0 and 10 are that unlikely.
+        assert!(close(binomial_test(10, 0, 0.5), 2.0 / 1024.0));
+        // 0..=3 and 17..=20 successes.
+        let tail = 1.0 + 20.0 + 190.0 + 1140.0;
+        assert!(close(binomial_test(20, 3, 0.5), 2.0 * tail / 1_048_576.0));
+        // Lopsided: 3 of 4 at 75% is as likely as it gets.
+        assert!(close(binomial_test(4, 3, 0.75), 1.0));
+    }
+
+    #[test]
+    fn binomial_test_handles_certain_outcomes() {
+        assert_eq!(binomial_test(5, 0, 0.0), 1.0);
+        assert_eq!(binomial_test(5, 1, 0.0), 0.0);
+        assert_eq!(binomial_test(5, 5, 1.0), 1.0);
+        assert_eq!(binomial_test(5, 4, 1.0), 0.0);
+    }
+
+    #[test]
+    fn clicks_are_grouped_by_chance_in_order() {
+        let clicks = [
+            (75, true),
+            (25, false),
+            (75, false),
+            (25, false),
+            (45, true),
+        ]
+        .iter()
+        .map(|&(percent, success)| (Chance::from_percent(percent), success));
+        let fits = goodness_of_fit(clicks);
+        let summary = fits
+            .iter()
+            .map(|f| (f.chance.percent(), f.clicks, f.successes))
+            .collect::<Vec<_>>();
+        assert_eq!(summary, [(25, 2, 0), (45, 1, 1), (75, 2, 1)]);
+        assert!(close(fits[0].p_value, 1.0));
+        assert_eq!(fits[2].observed_rate(), 0.5);
+    }
+}
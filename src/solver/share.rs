@@ -0,0 +1,317 @@
+use std::{fmt, str::FromStr};
+
+use super::{
+    chance::{Chance, ChanceLadder},
+    game_state::{GameState, Move, MAX_SLOTS},
+    solution::{Goal, Scoring, TABLE_LEN},
+};
+
+/// A stone and the scoring to judge it by, written as a short string that
+/// only uses characters that are safe in a URL fragment:
+///
+/// ```text
+/// 1_8_25,75,75,-10,10_AAb55Ca_w1.1,1,-1,0,0,0
+/// ```
+///
+/// The fields are the format version, the slots per row, the chance ladder,
+/// the move history and the scoring. Each click is a letter for its row,
+/// upper case for a success and lower case for a failure; a number is a
+/// chance set by hand. The scoring is `w` followed by the success and then
+/// failure points, `g` followed by a goal, or `t` followed by a payoff table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedStone {
+    pub game_state: GameState,
+    pub scoring: Scoring,
+}
+
+const VERSION: &str = "1";
+
+const ROW_LETTERS: [char; 3] = ['a', 'b', 'c'];
+
+impl fmt::Display for SharedStone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gs = &self.game_state;
+        let ladder = gs.ladder();
+        write!(
+            f,
+            "{}_{}_{},{},{},{},{}_",
+            VERSION,
+            gs.num_slots(),
+            ladder.min,
+            ladder.max,
+            ladder.start,
+            ladder.on_success,
+            ladder.on_fail
+        )?;
+
+        let mut after_number = false;
+        for m in gs.history() {
+            match *m {
+                Move::Click { row, success } => {
+                    let letter = ROW_LETTERS[row];
+                    if success {
+                        write!(f, "{}", letter.to_ascii_uppercase())?;
+                    } else {
+                        write!(f, "{}", letter)?;
+                    }
+                    after_number = false;
+                }
+                Move::SetChance(chance) => {
+                    // Two chances in a row would run together.
+                    if after_number {
+                        write!(f, ".")?;
+                    }
+                    write!(f, "{}", chance.percent())?;
+                    after_number = true;
+                }
+            }
+        }
+        write!(f, "_")?;
+        write_scoring(f, &self.scoring)
+    }
+}
+
+/// Writes `scoring` as `w` followed by the success and then failure points,
+/// `g` followed by a goal, or `t` followed by a payoff table.
+pub(super) fn write_scoring(f: &mut impl fmt::Write, scoring: &Scoring) -> fmt::Result {
+    let (kind, values) = match scoring {
+        Scoring::Weighted { success, fail } => ('w', success.iter().chain(fail).copied().collect()),
+        Scoring::Goal(goal) => (
+            'g',
+            vec![
+                f64::from(goal.min_skills[0]),
+                f64::from(goal.min_skills[1]),
+                f64::from(goal.max_negative),
+            ],
+        ),
+        Scoring::Table { payoff } => ('t', payoff.iter().flatten().copied().collect::<Vec<_>>()),
+    };
+    write!(f, "{}", kind)?;
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            write!(f, ",")?;
+        }
+        write!(f, "{}", value)?;
+    }
+    Ok(())
+}
+
+impl FromStr for SharedStone {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s.trim().split('_').collect::<Vec<_>>();
+        if fields.first() != Some(&VERSION) {
+            return Err("not a stone from this version of the optimizer".to_string());
+        }
+        let (num_slots, ladder, moves, scoring) = match fields[1..] {
+            [num_slots, ladder, moves, scoring] => (num_slots, ladder, moves, scoring),
+            _ => return Err("wrong number of fields".to_string()),
+        };
+
+        let num_slots: u8 = parse_number("slot count", num_slots)?;
+        if num_slots == 0 || num_slots > MAX_SLOTS {
+            return Err(format!("slot count must be between 1 and {}", MAX_SLOTS));
+        }
+        let ladder = match ladder.split(',').collect::<Vec<_>>()[..] {
+            [min, max, start, on_success, on_fail] => ChanceLadder {
+                min: parse_percent("chance rules", min)?,
+                max: parse_percent("chance rules", max)?,
+                start: parse_percent("chance rules", start)?,
+                on_success: parse_number("chance rules", on_success)?,
+                on_fail: parse_number("chance rules", on_fail)?,
+            },
+            _ => return Err("chance rules need five values".to_string()),
+        };
+
+        let mut game_state = GameState::new(num_slots, ladder);
+        let levels = ladder.levels();
+        let mut chars = moves.chars().peekable();
+        while let Some(c) = chars.next() {
+            if let Some(row) = ROW_LETTERS
+                .iter()
+                .position(|&l| l == c.to_ascii_lowercase())
+            {
+                if game_state.row(row).len() == usize::from(num_slots) {
+                    return Err("more clicks than slots".to_string());
+                }
+                game_state.record(row, c.is_ascii_uppercase());
+            } else if c.is_ascii_digit() {
+                let mut percent = c.to_string();
+                while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    percent.push(d);
+                    chars.next();
+                }
+                let chance = Chance::from_percent(parse_percent("chance", &percent)?);
+                if !levels.contains(&chance) {
+                    return Err(format!(
+                        "a {} chance can't come up with these rules",
+                        chance
+                    ));
+                }
+                game_state.set_chance(chance);
+            } else if c != '.' {
+                return Err(format!("unknown move {}", c));
+            }
+        }
+
+        Ok(Self {
+            game_state,
+            scoring: parse_scoring(scoring)?,
+        })
+    }
+}
+
+/// Reads a scoring written by `write_scoring`.
+pub(super) fn parse_scoring(s: &str) -> Result<Scoring, String> {
+    let mut scoring_chars = s.chars();
+    let kind = scoring_chars.next();
+    let values = scoring_chars.as_str().split(',').collect::<Vec<_>>();
+    let scoring = match (kind, values.len()) {
+        (Some('w'), 6) => {
+            let values = parse_points(&values)?;
+            Scoring::Weighted {
+                success: [values[0], values[1], values[2]],
+                fail: [values[3], values[4], values[5]],
+            }
+        }
+        (Some('g'), 3) => {
+            let values = values
+                .iter()
+                .map(|v| {
+                    let count: u8 = parse_number("goal", v)?;
+                    if count > MAX_SLOTS {
+                        return Err(format!("goal values must be at most {}", MAX_SLOTS));
+                    }
+                    Ok(count)
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            Scoring::Goal(Goal {
+                min_skills: [values[0], values[1]],
+                max_negative: values[2],
+            })
+        }
+        (Some('t'), n) if n == 3 * TABLE_LEN => {
+            let values = parse_points(&values)?;
+            let mut payoff = [[0.0; TABLE_LEN]; 3];
+            for (i, &value) in values.iter().enumerate() {
+                payoff[i / TABLE_LEN][i % TABLE_LEN] = value;
+            }
+            Scoring::Table { payoff }
+        }
+        _ => return Err("unknown scoring".to_string()),
+    };
+    Ok(scoring)
+}
+
+fn parse_number<T: FromStr>(what: &str, s: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("invalid {}: {}", what, s))
+}
+
+/// Reads a percentage, which can't be over 100.
+fn parse_percent(what: &str, s: &str) -> Result<u8, String> {
+    match parse_number(what, s)? {
+        percent @ 0..=100 => Ok(percent),
+        _ => Err(format!("invalid {}: {} is over 100%", what, s)),
+    }
+}
+
+/// Reads scoring points, which have to be finite.
+fn parse_points(values: &[&str]) -> Result<Vec<f64>, String> {
+    values
+        .iter()
+        .map(|v| {
+            let v: f64 = parse_number("scoring", v)?;
+            if v.is_finite() {
+                Ok(v)
+            } else {
+                Err(format!("invalid scoring: {}", v))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stone(code: &str) -> Result<SharedStone, String> {
+        code.parse()
+    }
+
+    #[test]
+    fn codes_round_trip() {
+        let mut game_state = GameState::new(10, ChanceLadder::default());
+        game_state.record(0, true);
+        game_state.record(2, false);
+        game_state.set_chance(Chance::from_percent(45));
+        game_state.set_chance(Chance::from_percent(55));
+        game_state.record(1, true);
+        let mut payoff = [[0.0; TABLE_LEN]; 3];
+        payoff[0][7] = 1.5;
+        payoff[2][5] = -2.0;
+        for scoring in [
+            Scoring::Weighted {
+                success: [1.1, 1.0, -1.0],
+                fail: [0.0, 0.0, 0.25],
+            },
+            Scoring::Goal(Goal {
+                min_skills: [7, 6],
+                max_negative: 4,
+            }),
+            Scoring::Table { payoff },
+        ] {
+            let shared = SharedStone {
+                game_state: game_state.clone(),
+                scoring,
+            };
+            assert_eq!(stone(&shared.to_string()), Ok(shared));
+        }
+    }
+
+    #[test]
+    fn parses_a_documented_code() {
+        let shared = stone("1_8_25,75,75,-10,10_AAb55Ca_w1.1,1,-1,0,0,0").unwrap();
+        assert_eq!(shared.game_state.row(0), [true, true, false]);
+        assert_eq!(shared.game_state.row(1), [false]);
+        assert_eq!(shared.game_state.row(2), [true]);
+        assert_eq!(shared.game_state.chance(), Chance::from_percent(55));
+    }
+
+    #[test]
+    fn rejects_bad_codes() {
+        for code in [
+            // Version and shape.
+            "2_8_25,75,75,-10,10__g7,7,4",
+            "1_8_25,75,75,-10,10_g7,7,4",
+            // Slot counts.
+            "1_0_25,75,75,-10,10__g7,7,4",
+            "1_17_25,75,75,-10,10__g7,7,4",
+            "1_1e9_25,75,75,-10,10__g7,7,4",
+            "1_8.5_25,75,75,-10,10__g7,7,4",
+            // Chance rules.
+            "1_8_25,75,75,-10__g7,7,4",
+            "1_8_25,175,75,-10,10__g7,7,4",
+            "1_8_25,75,75,-10,300__g7,7,4",
+            "1_8_25.5,75,75,-10,10__g7,7,4",
+            "1_8_-25,75,75,-10,10__g7,7,4",
+            // Moves.
+            "1_8_25,75,75,-10,10_AAAAAAAAA_g7,7,4",
+            "1_8_25,75,75,-10,10_a50_g7,7,4",
+            "1_8_25,75,75,-10,10_a300_g7,7,4",
+            "1_8_25,75,75,-10,10_ad_g7,7,4",
+            // Scorings.
+            "1_8_25,75,75,-10,10__x7,7,4",
+            "1_8_25,75,75,-10,10__g7,7",
+            "1_8_25,75,75,-10,10__g300,-5,1",
+            "1_8_25,75,75,-10,10__g7.5,7,4",
+            "1_8_25,75,75,-10,10__g17,7,4",
+            "1_8_25,75,75,-10,10__w1,1,-1,0,0,inf",
+            "1_8_25,75,75,-10,10__w1,1,-1,0,0,NaN",
+            "1_8_25,75,75,-10,10__w1,1,-1,0,0,",
+            "1_8_25,75,75,-10,10__t1,2,3",
+        ] {
+            assert!(stone(code).is_err(), "accepted {}", code);
+        }
+    }
+}
@@ -0,0 +1,1385 @@
+use arrayvec::ArrayVec;
+use fnv::FnvHashMap;
+use rand::prelude::*;
+use std::{borrow::Cow, fmt, mem};
+
+use super::{
+    chance::{Chance, ChanceLadder},
+    game_state::{GameState, MAX_SLOTS},
+    policy::Policy,
+    rates::SuccessRates,
+    SimResult,
+};
+
+/// How good clicking one row is, assuming every later click is optimal.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy)]
+pub struct Answer {
+    /// The row to click: 0 and 1 are the skills, 2 is the negative.
+    pub index: usize,
+    /// The value the solver maximizes; see `Risk`.
+    pub score: f64,
+    /// Expected final score.
+    pub mean: f64,
+    /// Standard deviation of the final score, if the risk setting uses it.
+    pub std_dev: Option<f64>,
+}
+
+/// The first two moments of the score still to come from some state.
+#[derive(Debug, Clone, Copy)]
+struct Outlook {
+    mean: f64,
+    variance: f64,
+}
+
+impl Outlook {
+    fn certain(value: f64) -> Self {
+        Self {
+            mean: value,
+            variance: 0.0,
+        }
+    }
+
+    fn shifted(self, by: f64) -> Self {
+        Self {
+            mean: self.mean + by,
+            variance: self.variance,
+        }
+    }
+
+    /// Mean and standard deviation of a click that leads to `success` with
+    /// probability `prob_success` and to `fail` otherwise.
+    fn mix(prob_success: f64, success: Self, fail: Self) -> (f64, f64) {
+        let prob_fail = 1.0 - prob_success;
+        let mean = prob_success * success.mean + prob_fail * fail.mean;
+        let second_moment = prob_success * (success.variance + success.mean * success.mean)
+            + prob_fail * (fail.variance + fail.mean * fail.mean);
+        (mean, (second_moment - mean * mean).max(0.0).sqrt())
+    }
+}
+
+/// Every distinct score still to come from some state along with its
+/// probability, sorted by score.
+#[derive(Debug, Clone)]
+struct Distribution(Vec<(f64, f64)>);
+
+// Scores closer together than this are treated as the same score, so that
+// floating point noise doesn't keep distributions from collapsing.
+const SAME_SCORE: f64 = 1e-9;
+
+impl Distribution {
+    fn certain(value: f64) -> Self {
+        Self(vec![(value, 1.0)])
+    }
+
+    /// Distribution of a click that earns `success_reward` and leads to
+    /// `success` with probability `prob_success`, and otherwise earns
+    /// `fail_reward` and leads to `fail`.
+    fn mix(
+        prob_success: f64,
+        (success, success_reward): (&Self, f64),
+        (fail, fail_reward): (&Self, f64),
+    ) -> Self {
+        let mut success = success
+            .0
+            .iter()
+            .map(|&(score, p)| (score + success_reward, p * prob_success))
+            .peekable();
+        let mut fail = fail
+            .0
+            .iter()
+            .map(|&(score, p)| (score + fail_reward, p * (1.0 - prob_success)))
+            .peekable();
+
+        let mut out: Vec<(f64, f64)> = Vec::with_capacity(success.len() + fail.len());
+        loop {
+            let (score, p) = match (success.peek(), fail.peek()) {
+                (Some(a), Some(b)) if a.0 <= b.0 => success.next(),
+                (_, Some(_)) => fail.next(),
+                (Some(_), None) => success.next(),
+                (None, None) => break,
+            }
+            .unwrap();
+            if p == 0.0 {
+                continue;
+            }
+            match out.last_mut() {
+                Some(last) if score - last.0 <= SAME_SCORE => last.1 += p,
+                _ => out.push((score, p)),
+            }
+        }
+        Self(out)
+    }
+
+    /// Average over the lowest `alpha` fraction of outcomes.
+    fn cvar(&self, alpha: f64) -> f64 {
+        let mut remaining = alpha;
+        let mut total = 0.0;
+        for &(score, p) in &self.0 {
+            let p = p.min(remaining);
+            total += p * score;
+            remaining -= p;
+            if remaining <= 0.0 {
+                break;
+            }
+        }
+        // Rounding can leave the probabilities summing to slightly under 1.
+        total / (alpha - remaining)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct State {
+    chance: Chance,
+    remaining: [u8; 3],
+    // Number of successes in each row so far, capped at `Solution::caps`.
+    // Scorings that only care about per-click rewards have all caps set to
+    // 0, so they don't pay for tracking successes at all.
+    successes: [u8; 3],
+}
+
+impl State {
+    fn new(gs: &GameState, caps: [u8; 3]) -> Self {
+        let num_slots = gs.num_slots();
+        let mut successes = count_successes(gs);
+        for i in 0..3 {
+            successes[i] = successes[i].min(caps[i]);
+        }
+        Self {
+            chance: gs.chance(),
+            remaining: [
+                num_slots - gs.row(0).len() as u8,
+                num_slots - gs.row(1).len() as u8,
+                num_slots - gs.row(2).len() as u8,
+            ],
+            successes,
+        }
+    }
+
+    fn available_choices(&self) -> ArrayVec<usize, 3> {
+        let mut out = ArrayVec::new();
+        for i in 0..3 {
+            if self.remaining[i] > 0 {
+                out.push(i);
+            }
+        }
+        out
+    }
+
+    fn transition(&self, choice: usize, caps: [u8; 3], ladder: &ChanceLadder) -> (Self, Self) {
+        assert!(self.remaining[choice] > 0);
+        let mut success = *self;
+        success.remaining[choice] -= 1;
+        let mut fail = success;
+
+        success.chance = ladder.after(self.chance, true);
+        success.successes[choice] = (success.successes[choice] + 1).min(caps[choice]);
+        fail.chance = ladder.after(self.chance, false);
+
+        (success, fail)
+    }
+
+    fn update(
+        &mut self,
+        choice: usize,
+        caps: [u8; 3],
+        ladder: &ChanceLadder,
+        prob_success: f64,
+        rng: &mut impl Rng,
+    ) -> bool {
+        assert!(self.remaining[choice] > 0);
+        self.remaining[choice] -= 1;
+        let success = rng.gen::<f64>() < prob_success;
+        if success {
+            self.successes[choice] = (self.successes[choice] + 1).min(caps[choice]);
+        }
+        self.chance = ladder.after(self.chance, success);
+        success
+    }
+}
+
+/// What makes a finished stone good.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[allow(clippy::large_enum_variant)]
+pub enum Scoring {
+    /// Fixed point values for every success or failure in each row; the
+    /// solver maximizes the expected total.
+    Weighted { success: [f64; 3], fail: [f64; 3] },
+    /// The solver maximizes the probability of the finished stone meeting
+    /// every threshold of the goal.
+    Goal(Goal),
+    /// `payoff[row][n]` is the value of finishing with `n` successes in
+    /// `row`; the solver maximizes the expected sum over all three rows.
+    Table { payoff: [[f64; TABLE_LEN]; 3] },
+}
+
+/// Number of entries in each row of a `Scoring::Table`: one for every
+/// possible final count on the largest supported stone.
+pub const TABLE_LEN: usize = MAX_SLOTS as usize + 1;
+
+/// Minimum successes in each skill row and maximum successes in the
+/// negative row.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Goal {
+    pub min_skills: [u8; 2],
+    pub max_negative: u8,
+}
+
+impl Goal {
+    /// Whether a stone that ended with `successes` in each row meets the
+    /// goal.
+    pub fn is_met(&self, successes: [u8; 3]) -> bool {
+        successes[0] >= self.min_skills[0]
+            && successes[1] >= self.min_skills[1]
+            && successes[2] <= self.max_negative
+    }
+}
+
+impl Scoring {
+    /// How many successes per row the solver needs to distinguish between to
+    /// score a stone with `count` slots per row, and the reward for each
+    /// success in a row once its count has reached that cap.
+    fn tracking(&self, count: u8) -> ([u8; 3], [f64; 3]) {
+        match self {
+            Scoring::Weighted { .. } => ([0, 0, 0], [0.0, 0.0, 0.0]),
+            Scoring::Goal(goal) => (
+                [
+                    goal.min_skills[0].min(count),
+                    goal.min_skills[1].min(count),
+                    // Once we're over the negative limit, further successes
+                    // don't matter; if the limit is unreachable, nothing does.
+                    if goal.max_negative < count {
+                        goal.max_negative + 1
+                    } else {
+                        0
+                    },
+                ],
+                [0.0, 0.0, 0.0],
+            ),
+            Scoring::Table { payoff } => {
+                let mut caps = [0; 3];
+                let mut past_cap = [0.0; 3];
+                for i in 0..3 {
+                    // Past the last breakpoint every success is worth the same
+                    // amount, so we can stop counting there and hand out that
+                    // amount per click instead.
+                    let row = &payoff[i][..=usize::from(count)];
+                    let step = |n: usize| row[n + 1] - row[n];
+                    let mut cap = usize::from(count);
+                    if cap > 0 {
+                        let last_step = step(cap - 1);
+                        while cap > 0 && step(cap - 1) == last_step {
+                            cap -= 1;
+                        }
+                        past_cap[i] = last_step;
+                    }
+                    caps[i] = cap as u8;
+                }
+                (caps, past_cap)
+            }
+        }
+    }
+
+    fn reward(&self, row: usize, success: bool) -> f64 {
+        match self {
+            Scoring::Weighted {
+                success: s,
+                fail: f,
+            } => {
+                if success {
+                    s[row]
+                } else {
+                    f[row]
+                }
+            }
+            Scoring::Goal(_) | Scoring::Table { .. } => 0.0,
+        }
+    }
+
+    fn terminal(&self, successes: [u8; 3]) -> f64 {
+        match self {
+            Scoring::Weighted { .. } => 0.0,
+            Scoring::Goal(goal) => {
+                if goal.is_met(successes) {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Scoring::Table { payoff } => {
+                payoff[0][usize::from(successes[0])]
+                    + payoff[1][usize::from(successes[1])]
+                    + payoff[2][usize::from(successes[2])]
+            }
+        }
+    }
+
+    fn eval(&self, scores: [u8; 3], count: u8) -> f64 {
+        match self {
+            Scoring::Weighted { success, fail } => {
+                success[0] * f64::from(scores[0])
+                    + success[1] * f64::from(scores[1])
+                    + success[2] * f64::from(scores[2])
+                    + fail[0] * (f64::from(count) - f64::from(scores[0]))
+                    + fail[1] * (f64::from(count) - f64::from(scores[1]))
+                    + fail[2] * (f64::from(count) - f64::from(scores[2]))
+            }
+            Scoring::Goal(_) | Scoring::Table { .. } => self.terminal(scores),
+        }
+    }
+
+    /// Whether scores produced under this scoring are probabilities rather
+    /// than point totals.
+    pub fn is_probability(&self) -> bool {
+        matches!(self, Scoring::Goal(_))
+    }
+}
+
+/// How the solver trades the expected final score against its spread.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Risk {
+    /// Maximize the expected final score.
+    Neutral,
+    /// Maximize the expected final score minus `lambda` standard deviations.
+    ///
+    /// Each click is chosen to maximize this for the rest of the stone given
+    /// how later clicks will be chosen, which is not always the policy with
+    /// the best mean-deviation tradeoff over the whole stone.
+    MeanStdDev { lambda: f64 },
+    /// Maximize the conditional value at risk: the average final score over
+    /// the worst `alpha` fraction of outcomes.
+    ///
+    /// As with `MeanStdDev`, each click is chosen given how later clicks will
+    /// be chosen. This needs the full distribution of scores from every
+    /// state, so it's considerably slower to solve than the other options.
+    Cvar { alpha: f64 },
+}
+
+impl Risk {
+    /// Whether the solver needs the spread of scores as well as the mean.
+    fn uses_spread(self) -> bool {
+        self != Risk::Neutral
+    }
+}
+
+/// Maps every `State` of a stone to a distinct position in a flat table.
+///
+/// Each row contributes a (remaining, successes) pair, and successes can't
+/// exceed either the row's cap or the number of slots already clicked, so
+/// only the pairs that can actually occur get a position.
+#[derive(Debug)]
+struct StateIndex {
+    // Position of each chance (by percent) in the ladder's levels.
+    chances: [Option<usize>; 101],
+    // `row_offsets[row][remaining]` is the position of the pair
+    // (remaining, 0) among the row's pairs.
+    row_offsets: [Vec<usize>; 3],
+    row_sizes: [usize; 3],
+    len: usize,
+}
+
+impl StateIndex {
+    fn new(levels: &[Chance], count: u8, caps: [u8; 3]) -> Self {
+        let mut chances = [None; 101];
+        for (i, chance) in levels.iter().enumerate() {
+            chances[usize::from(chance.percent())] = Some(i);
+        }
+
+        let mut row_offsets: [Vec<usize>; 3] = Default::default();
+        let mut row_sizes = [0; 3];
+        for row in 0..3 {
+            for remaining in 0..=count {
+                row_offsets[row].push(row_sizes[row]);
+                row_sizes[row] += usize::from(caps[row].min(count - remaining)) + 1;
+            }
+        }
+
+        Self {
+            chances,
+            row_offsets,
+            row_sizes,
+            len: levels.len() * row_sizes.iter().product::<usize>(),
+        }
+    }
+
+    /// Position of `state`, or `None` if its chance can't come up.
+    fn get(&self, state: &State) -> Option<usize> {
+        let mut index = self.chances[usize::from(state.chance.percent())]?;
+        for row in 0..3 {
+            let pair = self.row_offsets[row][usize::from(state.remaining[row])]
+                + usize::from(state.successes[row]);
+            index = index * self.row_sizes[row] + pair;
+        }
+        Some(index)
+    }
+}
+
+/// Returned by the `_with_progress` methods of [`Solution`] when their
+/// progress callback asks them to stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cancelled")
+    }
+}
+
+/// Why a [`Solution`] couldn't be built.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+    /// The progress callback asked to stop.
+    Cancelled,
+    /// The stone has more slots per row than `MAX_SLOTS`.
+    TooManySlots(u8),
+    /// Some state's score isn't a number, e.g. because weights too large
+    /// for an `f64` cancelled out.
+    NotANumber,
+}
+
+impl From<Cancelled> for BuildError {
+    fn from(Cancelled: Cancelled) -> Self {
+        BuildError::Cancelled
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Cancelled => write!(f, "cancelled"),
+            BuildError::TooManySlots(count) => {
+                write!(f, "too many slots: {} (at most {})", count, MAX_SLOTS)
+            }
+            BuildError::NotANumber => write!(f, "the scores aren't numbers; try smaller weights"),
+        }
+    }
+}
+
+/// Simulated runs from one state, which more runs can be added to.
+#[derive(Debug, Clone, Default)]
+pub struct SimTally {
+    counts: FnvHashMap<[u8; 3], u32>,
+    runs: u32,
+}
+
+impl SimTally {
+    /// Number of runs so far.
+    pub fn runs(&self) -> u32 {
+        self.runs
+    }
+}
+
+// Stands in for the best row of a state with nothing left to click.
+const FINISHED: u8 = u8::MAX;
+
+/// The best click from every state of a stone, for one scoring, risk setting,
+/// chance ladder, set of success rates and number of slots per row.
+#[derive(Debug)]
+pub struct Solution {
+    scoring: Scoring,
+    risk: Risk,
+    ladder: ChanceLadder,
+    levels: Vec<Chance>,
+    // Probability of success at each chance, by percent.
+    success_rates: [f64; 101],
+    index: StateIndex,
+    // The best row to click from each state, at the position `index` gives
+    // it, or `FINISHED`.
+    best: Vec<u8>,
+    // Mean and standard deviation of the score still to come from each state
+    // when playing optimally. Standard deviations are only kept if the risk
+    // setting uses them; everything else about a state's choices can be
+    // worked out again from its successors.
+    means: Vec<f64>,
+    std_devs: Vec<f64>,
+    // Score of clicking each row from each state under `Risk::Cvar`, which
+    // depends on whole distributions rather than moments.
+    cvar_scores: Vec<[f64; 3]>,
+    count: u8,
+    caps: [u8; 3],
+    past_cap: [f64; 3],
+}
+
+impl Solution {
+    /// Solves every state of a stone with `count` slots per row.
+    ///
+    /// Never returns [`BuildError::Cancelled`].
+    pub fn build(
+        scoring: Scoring,
+        risk: Risk,
+        ladder: ChanceLadder,
+        rates: &SuccessRates,
+        count: u8,
+    ) -> Result<Self, BuildError> {
+        Self::build_with_progress(scoring, risk, ladder, rates, count, |_| true)
+    }
+
+    /// Like [`build`](Self::build), but calls `progress` every so often with
+    /// the fraction of the work done so far. Stops early if `progress`
+    /// returns false.
+    pub fn build_with_progress(
+        scoring: Scoring,
+        risk: Risk,
+        ladder: ChanceLadder,
+        rates: &SuccessRates,
+        count: u8,
+        mut progress: impl FnMut(f64) -> bool,
+    ) -> Result<Self, BuildError> {
+        if count > MAX_SLOTS {
+            return Err(BuildError::TooManySlots(count));
+        }
+        let (caps, past_cap) = scoring.tracking(count);
+        let levels = ladder.levels();
+        let index = StateIndex::new(&levels, count, caps);
+        let mut success_rates = [0.0; 101];
+        for (percent, rate) in (0..).zip(&mut success_rates) {
+            *rate = rates.rate(Chance::from_percent(percent));
+        }
+        let mut this = Self {
+            scoring,
+            risk,
+            ladder,
+            levels,
+            success_rates,
+            best: vec![FINISHED; index.len],
+            means: vec![0.0; index.len],
+            std_devs: if risk.uses_spread() {
+                vec![0.0; index.len]
+            } else {
+                Vec::new()
+            },
+            cvar_scores: if matches!(risk, Risk::Cvar { .. }) {
+                vec![[0.0; 3]; index.len]
+            } else {
+                Vec::new()
+            },
+            index,
+            count,
+            caps,
+            past_cap,
+        };
+        this.build_impl(&mut progress)?;
+        Ok(this)
+    }
+
+    /// Number of states the solution covers.
+    pub fn num_states(&self) -> usize {
+        self.best.iter().filter(|&&best| best != FINISHED).count()
+    }
+
+    /// Bytes the tables of a solution with these inputs take, which is known
+    /// without building it.
+    pub fn memory_needed(scoring: Scoring, risk: Risk, ladder: ChanceLadder, count: u8) -> usize {
+        if count > MAX_SLOTS {
+            return 0;
+        }
+        let (caps, _) = scoring.tracking(count);
+        StateIndex::new(&ladder.levels(), count, caps).len * Self::bytes_per_state(risk)
+    }
+
+    /// Bytes the solution's tables take.
+    pub fn memory_size(&self) -> usize {
+        self.index.len * Self::bytes_per_state(self.risk)
+    }
+
+    fn bytes_per_state(risk: Risk) -> usize {
+        let mut bytes = mem::size_of::<u8>() + mem::size_of::<f64>();
+        if risk.uses_spread() {
+            bytes += mem::size_of::<f64>();
+        }
+        if let Risk::Cvar { .. } = risk {
+            bytes += mem::size_of::<[f64; 3]>();
+        }
+        bytes
+    }
+
+    fn build_impl(&mut self, progress: &mut impl FnMut(f64) -> bool) -> Result<(), BuildError> {
+        // Every transition uses up one slot, so solving states in order of how
+        // many slots they have left guarantees successors are solved before
+        // the states that lead to them. It also means we only need to hold on
+        // to one layer of score distributions at a time.
+        let levels = self.levels.clone();
+        let mut solved = 0;
+        let mut prev_layer = FnvHashMap::default();
+        for slots_left in 0..=3 * usize::from(self.count) {
+            let mut layer = FnvHashMap::default();
+            for remaining in triples_up_to([self.count; 3]) {
+                if remaining.iter().map(|&r| usize::from(r)).sum::<usize>() != slots_left {
+                    continue;
+                }
+                let max_successes = [
+                    self.caps[0].min(self.count - remaining[0]),
+                    self.caps[1].min(self.count - remaining[1]),
+                    self.caps[2].min(self.count - remaining[2]),
+                ];
+                for successes in triples_up_to(max_successes) {
+                    for &chance in &levels {
+                        let state = State {
+                            chance,
+                            remaining,
+                            successes,
+                        };
+                        self.solve_state(state, &prev_layer, &mut layer)?;
+                        solved += 1;
+                    }
+                }
+                if !progress(solved as f64 / self.index.len as f64) {
+                    return Err(BuildError::Cancelled);
+                }
+            }
+            prev_layer = layer;
+        }
+        Ok(())
+    }
+
+    /// Solves `state`, whose successors' distributions (if the risk setting
+    /// needs them) are in `prev_layer`. Adds the distribution of `state`
+    /// itself to `layer`.
+    fn solve_state(
+        &mut self,
+        state: State,
+        prev_layer: &FnvHashMap<State, Distribution>,
+        layer: &mut FnvHashMap<State, Distribution>,
+    ) -> Result<(), BuildError> {
+        let available_choices = state.available_choices();
+        if available_choices.is_empty() {
+            return Ok(());
+        }
+
+        let mut distributions = ArrayVec::<_, 3>::new();
+        let answers = self.rank_choices(&state, |index| {
+            let alpha = match self.risk {
+                Risk::Cvar { alpha } => alpha,
+                _ => return f64::NAN,
+            };
+            let (success_state, fail_state) = state.transition(index, self.caps, &self.ladder);
+            let distribution = Distribution::mix(
+                self.success_rate(state.chance),
+                (
+                    &self.distribution(&success_state, prev_layer),
+                    self.reward(&state, index, true),
+                ),
+                (
+                    &self.distribution(&fail_state, prev_layer),
+                    self.reward(&state, index, false),
+                ),
+            );
+            let score = distribution.cvar(alpha);
+            distributions.push((index, distribution));
+            score
+        });
+
+        // Anything NaN here would spread to every state before this one.
+        if answers
+            .iter()
+            .any(|a| a.score.is_nan() || a.mean.is_nan() || a.std_dev.is_some_and(f64::is_nan))
+        {
+            return Err(BuildError::NotANumber);
+        }
+        let best = answers[0];
+        if let Some((_, distribution)) = distributions
+            .into_iter()
+            .find(|(index, _)| *index == best.index)
+        {
+            layer.insert(state, distribution);
+        }
+        let i = self.index.get(&state).unwrap();
+        self.best[i] = best.index as u8;
+        self.means[i] = best.mean;
+        if let Some(std_dev) = best.std_dev {
+            self.std_devs[i] = std_dev;
+        }
+        if let Some(scores) = self.cvar_scores.get_mut(i) {
+            for answer in &answers {
+                scores[answer.index] = answer.score;
+            }
+        }
+        Ok(())
+    }
+
+    /// Every row that can be clicked from `state`, best first, once the
+    /// states it leads to are solved. `cvar` gives the score of clicking a
+    /// row under `Risk::Cvar`.
+    fn rank_choices(
+        &self,
+        state: &State,
+        mut cvar: impl FnMut(usize) -> f64,
+    ) -> ArrayVec<Answer, 3> {
+        let prob_success = self.success_rate(state.chance);
+        let mut answers = ArrayVec::<_, 3>::new();
+        for index in state.available_choices() {
+            let (success_state, fail_state) = state.transition(index, self.caps, &self.ladder);
+            let (mean, std_dev) = Outlook::mix(
+                prob_success,
+                self.outlook(&success_state)
+                    .shifted(self.reward(state, index, true)),
+                self.outlook(&fail_state)
+                    .shifted(self.reward(state, index, false)),
+            );
+            let score = match self.risk {
+                Risk::Neutral => mean,
+                Risk::MeanStdDev { lambda } => mean - lambda * std_dev,
+                Risk::Cvar { .. } => cvar(index),
+            };
+            answers.push(Answer {
+                index,
+                score,
+                mean,
+                std_dev: Some(std_dev).filter(|_| self.risk.uses_spread()),
+            });
+        }
+
+        // A NaN score fails the build in `solve_state`, which needs the
+        // answers sorted somehow first.
+        answers.sort_by(|b, a| {
+            a.score
+                .partial_cmp(&b.score)
+                .unwrap_or_else(|| a.score.total_cmp(&b.score))
+                .then(b.index.cmp(&a.index))
+        });
+        answers
+    }
+
+    fn distribution<'a>(
+        &self,
+        state: &State,
+        prev_layer: &'a FnvHashMap<State, Distribution>,
+    ) -> Cow<'a, Distribution> {
+        match prev_layer.get(state) {
+            Some(distribution) => Cow::Borrowed(distribution),
+            None => {
+                debug_assert!(state.available_choices().is_empty());
+                Cow::Owned(Distribution::certain(
+                    self.scoring.terminal(state.successes),
+                ))
+            }
+        }
+    }
+
+    /// Score earned by clicking `row` from `state`, not counting whatever the
+    /// scoring hands out once the stone is finished.
+    fn reward(&self, state: &State, row: usize, success: bool) -> f64 {
+        let mut reward = self.scoring.reward(row, success);
+        if success && state.successes[row] == self.caps[row] {
+            reward += self.past_cap[row];
+        }
+        reward
+    }
+
+    fn success_rate(&self, chance: Chance) -> f64 {
+        self.success_rates[usize::from(chance.percent())]
+    }
+
+    /// Score already earned by the clicks recorded in `gs`.
+    fn eval_partial(&self, gs: &GameState) -> f64 {
+        let mut score = 0.0;
+        for i in 0..3 {
+            let mut successes = 0;
+            for &succeeded in gs.row(i) {
+                score += self.scoring.reward(i, succeeded);
+                if succeeded {
+                    if successes == self.caps[i] {
+                        score += self.past_cap[i];
+                    } else {
+                        successes += 1;
+                    }
+                }
+            }
+        }
+        score
+    }
+
+    /// Score still to come from `state` when playing optimally.
+    fn outlook(&self, state: &State) -> Outlook {
+        match self.lookup(state) {
+            Some((i, _)) => {
+                let std_dev = self.std_devs.get(i).copied().unwrap_or(0.0);
+                Outlook {
+                    mean: self.means[i],
+                    variance: std_dev * std_dev,
+                }
+            }
+            None => Outlook::certain(self.scoring.terminal(state.successes)),
+        }
+    }
+
+    /// Position of `state` in the solution's tables and the best row to
+    /// click from it, or `None` if it's finished.
+    fn lookup(&self, state: &State) -> Option<(usize, usize)> {
+        if let Some(i) = self.index.get(state) {
+            if self.best[i] != FINISHED {
+                return Some((i, usize::from(self.best[i])));
+            }
+        }
+        assert!(
+            state.available_choices().is_empty(),
+            "bad lookup: {:?}",
+            state
+        );
+        None
+    }
+
+    /// Every row that can still be clicked from `state`, best first, with
+    /// scores that include what the recorded clicks already earned.
+    ///
+    /// Returns `None` if `state` is finished or isn't a stone this solution
+    /// was built for.
+    pub fn sorted_choices(&self, state: &GameState) -> Option<ArrayVec<Answer, 3>> {
+        // The UI may have moved on to a different stone than we were built for.
+        if state.num_slots() != self.count || !self.levels.contains(&state.chance()) {
+            return None;
+        }
+        let partial_score = self.eval_partial(state);
+        let state = State::new(state, self.caps);
+        let (i, best) = self.lookup(&state)?;
+        let mut answer = self.rank_choices(&state, |index| self.cvar_scores[i][index]);
+        debug_assert_eq!(answer[0].index, best);
+        for a in &mut answer {
+            a.score += partial_score;
+            a.mean += partial_score;
+        }
+        Some(answer)
+    }
+
+    /// Plays out the rest of `start` optimally with random clicks and returns
+    /// the number of successes in each row.
+    ///
+    /// Panics if `start` doesn't have the number of slots the solution was
+    /// built for.
+    pub fn simulate_once(&self, start: &GameState, rng: &mut impl Rng) -> [u8; 3] {
+        self.play_once(start, Policy::Optimal, rng)
+    }
+
+    /// Like [`simulate_once`](Self::simulate_once), but with every click
+    /// picked by `policy`.
+    fn play_once(&self, start: &GameState, policy: Policy, rng: &mut impl Rng) -> [u8; 3] {
+        assert_eq!(self.count, start.num_slots());
+        let mut state = State::new(start, self.caps);
+        let mut scores = count_successes(start);
+
+        while !state.available_choices().is_empty() {
+            let choice = match policy {
+                // lookup is guaranteed to succeed as long as we have at least
+                // one available choice
+                Policy::Optimal => self.lookup(&state).unwrap().1,
+                _ => {
+                    let clicks = policy.clicks(state.chance, state.remaining);
+                    let mut pick = rng.gen::<f64>();
+                    // Rounding can leave a sliver past the last click.
+                    let (choice, _) = clicks
+                        .iter()
+                        .find(|&&(_, p)| {
+                            pick -= p;
+                            pick < 0.0
+                        })
+                        .or_else(|| clicks.last())
+                        .unwrap();
+                    *choice
+                }
+            };
+            let prob_success = self.success_rate(state.chance);
+            let success = state.update(choice, self.caps, &self.ladder, prob_success, rng);
+            if success {
+                scores[choice] += 1;
+            }
+        }
+
+        scores
+    }
+
+    /// The ten most common results of `sim_tries` simulated runs from
+    /// `start`, with how often each came up.
+    ///
+    /// The runs only depend on `rng`, so a seeded one gives the same results
+    /// every time:
+    ///
+    /// ```
+    /// use lost_ark_refiner::solver::{GameState, Risk, Solution, SuccessRates, PRESETS};
+    /// use rand::SeedableRng;
+    /// use rand_chacha::ChaCha8Rng;
+    ///
+    /// let stone = GameState::default();
+    /// let solution = Solution::build(
+    ///     PRESETS[0].scoring,
+    ///     Risk::Neutral,
+    ///     *stone.ladder(),
+    ///     &SuccessRates::advertised(),
+    ///     stone.num_slots(),
+    /// )
+    /// .unwrap();
+    ///
+    /// let run = |seed| solution.simulate_top_10(1000, &stone, &mut ChaCha8Rng::seed_from_u64(seed));
+    /// let (first, second) = (run(7), run(7));
+    /// assert!(first
+    ///     .iter()
+    ///     .zip(&second)
+    ///     .all(|(a, b)| a.counts == b.counts && a.probability == b.probability));
+    /// ```
+    pub fn simulate_top_10(
+        &self,
+        sim_tries: u32,
+        start: &GameState,
+        rng: &mut impl Rng,
+    ) -> Vec<SimResult> {
+        let mut tally = SimTally::default();
+        self.simulate_more(&mut tally, sim_tries, start, rng);
+        let mut most_likely = self.outcomes_of(&tally);
+        most_likely.truncate(10);
+        most_likely
+    }
+
+    /// Simulates `sim_tries` runs from `start`. Calls `progress` every so
+    /// often with the fraction of runs done so far, and stops early if it
+    /// returns false.
+    pub fn simulate_with_progress(
+        &self,
+        sim_tries: u32,
+        start: &GameState,
+        rng: &mut impl Rng,
+        progress: impl FnMut(f64) -> bool,
+    ) -> Result<SimTally, Cancelled> {
+        self.simulate_policy_with_progress(sim_tries, start, Policy::Optimal, rng, progress)
+    }
+
+    /// Like [`simulate_with_progress`](Self::simulate_with_progress), but
+    /// with every click picked by `policy`. Much quicker than
+    /// [`policy_outcome_distribution_with_progress`](Self::policy_outcome_distribution_with_progress)
+    /// for [`Policy::Random`], which can reach every state.
+    pub fn simulate_policy_with_progress(
+        &self,
+        sim_tries: u32,
+        start: &GameState,
+        policy: Policy,
+        rng: &mut impl Rng,
+        mut progress: impl FnMut(f64) -> bool,
+    ) -> Result<SimTally, Cancelled> {
+        let mut tally = SimTally::default();
+        while tally.runs < sim_tries {
+            if !progress(f64::from(tally.runs) / f64::from(sim_tries)) {
+                return Err(Cancelled);
+            }
+            // A single run is too quick to be worth checking in after.
+            let runs = (sim_tries - tally.runs).min(1024);
+            self.play_more(&mut tally, runs, start, policy, rng);
+        }
+        Ok(tally)
+    }
+
+    /// Adds `runs` more simulated runs from `start` to `tally`, which should
+    /// only hold runs from `start`.
+    pub fn simulate_more(
+        &self,
+        tally: &mut SimTally,
+        runs: u32,
+        start: &GameState,
+        rng: &mut impl Rng,
+    ) {
+        self.play_more(tally, runs, start, Policy::Optimal, rng);
+    }
+
+    fn play_more(
+        &self,
+        tally: &mut SimTally,
+        runs: u32,
+        start: &GameState,
+        policy: Policy,
+        rng: &mut impl Rng,
+    ) {
+        for _ in 0..runs {
+            *tally
+                .counts
+                .entry(self.play_once(start, policy, rng))
+                .or_default() += 1;
+        }
+        tally.runs += runs;
+    }
+
+    /// Every result in `tally`, most common first, with how often each came
+    /// up.
+    pub fn outcomes_of(&self, tally: &SimTally) -> Vec<SimResult> {
+        let runs = f64::from(tally.runs);
+        self.ranked(
+            tally
+                .counts
+                .iter()
+                .map(|(&result, &count)| (result, f64::from(count) / runs)),
+            Some(tally.runs),
+        )
+    }
+
+    /// Exact probability of every final result when playing optimally from
+    /// `start`.
+    ///
+    /// Panics if `start` doesn't have the number of slots the solution was
+    /// built for.
+    pub fn outcome_distribution(&self, start: &GameState) -> FnvHashMap<[u8; 3], f64> {
+        self.outcome_distribution_with_progress(start, |_| true)
+            .unwrap_or_else(|Cancelled| unreachable!())
+    }
+
+    /// Like [`outcome_distribution`](Self::outcome_distribution), but calls
+    /// `progress` after every click with the fraction of the work done so
+    /// far. Stops early if `progress` returns false.
+    pub fn outcome_distribution_with_progress(
+        &self,
+        start: &GameState,
+        progress: impl FnMut(f64) -> bool,
+    ) -> Result<FnvHashMap<[u8; 3], f64>, Cancelled> {
+        assert_eq!(self.count, start.num_slots());
+        let mut frontier = FnvHashMap::default();
+        frontier.insert((State::new(start, self.caps), count_successes(start)), 1.0);
+        self.play_out(frontier, self.clicks_left(start), Policy::Optimal, progress)
+    }
+
+    /// Like [`outcome_distribution_with_progress`](Self::outcome_distribution_with_progress),
+    /// but with every click picked by `policy` instead.
+    ///
+    /// The solver's policy does at least as well as any other by the measure
+    /// it was built for:
+    ///
+    /// ```
+    /// use lost_ark_refiner::solver::{GameState, Risk, Solution, SuccessRates, POLICIES, PRESETS};
+    ///
+    /// let stone = GameState::default();
+    /// let solution = Solution::build(
+    ///     PRESETS[0].scoring,
+    ///     Risk::Neutral,
+    ///     *stone.ladder(),
+    ///     &SuccessRates::advertised(),
+    ///     stone.num_slots(),
+    /// )
+    /// .unwrap();
+    ///
+    /// let average = |policy| {
+    ///     solution
+    ///         .policy_outcome_distribution_with_progress(&stone, policy, |_| true)
+    ///         .unwrap()
+    ///         .into_iter()
+    ///         .map(|(result, p)| p * solution.eval_result(result))
+    ///         .sum::<f64>()
+    /// };
+    /// let best = average(POLICIES[0]);
+    /// assert!(POLICIES[1..].iter().all(|&policy| average(policy) <= best + 1e-9));
+    /// ```
+    pub fn policy_outcome_distribution_with_progress(
+        &self,
+        start: &GameState,
+        policy: Policy,
+        progress: impl FnMut(f64) -> bool,
+    ) -> Result<FnvHashMap<[u8; 3], f64>, Cancelled> {
+        assert_eq!(self.count, start.num_slots());
+        let mut frontier = FnvHashMap::default();
+        frontier.insert((State::new(start, self.caps), count_successes(start)), 1.0);
+        self.play_out(frontier, self.clicks_left(start), policy, progress)
+    }
+
+    /// Like [`outcome_distribution_with_progress`](Self::outcome_distribution_with_progress),
+    /// but clicks `choice` first, whether or not it's the best row.
+    ///
+    /// Panics if `choice` can't be clicked from `start`.
+    pub fn choice_outcome_distribution_with_progress(
+        &self,
+        start: &GameState,
+        choice: usize,
+        progress: impl FnMut(f64) -> bool,
+    ) -> Result<FnvHashMap<[u8; 3], f64>, Cancelled> {
+        assert_eq!(self.count, start.num_slots());
+        let state = State::new(start, self.caps);
+        assert!(
+            state.available_choices().contains(&choice),
+            "can't click row {} from {:?}",
+            choice,
+            state
+        );
+        let mut frontier = FnvHashMap::default();
+        self.click(state, count_successes(start), 1.0, choice, &mut frontier);
+        self.play_out(
+            frontier,
+            self.clicks_left(start) - 1,
+            Policy::Optimal,
+            progress,
+        )
+    }
+
+    fn clicks_left(&self, start: &GameState) -> usize {
+        (0..3)
+            .map(|i| usize::from(self.count) - start.row(i).len())
+            .sum()
+    }
+
+    /// Splits `probability` of being in `state` with `scores` successes
+    /// between the states clicking `choice` leads to, in `next`. Branches
+    /// that can't happen are left out, so impossible results never show up.
+    fn click(
+        &self,
+        state: State,
+        scores: [u8; 3],
+        probability: f64,
+        choice: usize,
+        next: &mut FnvHashMap<(State, [u8; 3]), f64>,
+    ) {
+        let prob_success = self.success_rate(state.chance);
+        let (success_state, fail_state) = state.transition(choice, self.caps, &self.ladder);
+        let mut success_scores = scores;
+        success_scores[choice] += 1;
+
+        for (key, p) in [
+            ((success_state, success_scores), probability * prob_success),
+            ((fail_state, scores), probability * (1.0 - prob_success)),
+        ] {
+            if p > 0.0 {
+                *next.entry(key).or_default() += p;
+            }
+        }
+    }
+
+    /// Plays every state in `frontier` out with `policy`, and returns the
+    /// probability of each final result. Every state must have
+    /// `clicks_left` clicks left.
+    fn play_out(
+        &self,
+        mut frontier: FnvHashMap<(State, [u8; 3]), f64>,
+        clicks_left: usize,
+        policy: Policy,
+        mut progress: impl FnMut(f64) -> bool,
+    ) -> Result<FnvHashMap<[u8; 3], f64>, Cancelled> {
+        // Every click uses up one slot, so we can push probability mass
+        // forward one click at a time; `frontier` holds every state reachable
+        // after the same number of clicks (along with the untruncated success
+        // counts, which `State` doesn't necessarily keep).
+        let mut outcomes = FnvHashMap::default();
+        let mut clicks = 0;
+        while !frontier.is_empty() {
+            if !progress(clicks as f64 / clicks_left.max(1) as f64) {
+                return Err(Cancelled);
+            }
+            clicks += 1;
+            let mut next = FnvHashMap::default();
+            for ((state, scores), probability) in frontier {
+                if state.available_choices().is_empty() {
+                    *outcomes.entry(scores).or_default() += probability;
+                    continue;
+                }
+                match policy {
+                    Policy::Optimal => {
+                        // lookup is guaranteed to succeed as long as we have
+                        // at least one available choice
+                        let (_, best) = self.lookup(&state).unwrap();
+                        self.click(state, scores, probability, best, &mut next);
+                    }
+                    _ => {
+                        for (choice, p) in policy.clicks(state.chance, state.remaining) {
+                            self.click(state, scores, probability * p, choice, &mut next);
+                        }
+                    }
+                }
+            }
+            frontier = next;
+        }
+
+        Ok(outcomes)
+    }
+
+    /// The ten most likely final results from `start`.
+    pub fn exact_top_10(&self, start: &GameState) -> Vec<SimResult> {
+        let mut most_likely = self.rank(self.outcome_distribution(start));
+        most_likely.truncate(10);
+        most_likely
+    }
+
+    /// Every result in `outcomes`, as returned by
+    /// [`outcome_distribution`](Self::outcome_distribution), most likely
+    /// first.
+    pub fn rank(&self, outcomes: FnvHashMap<[u8; 3], f64>) -> Vec<SimResult> {
+        self.ranked(outcomes.into_iter(), None)
+    }
+
+    /// Every one of `outcomes`, most likely first. They were estimated from
+    /// `runs` simulated runs if they weren't worked out exactly.
+    fn ranked(
+        &self,
+        outcomes: impl Iterator<Item = ([u8; 3], f64)>,
+        runs: Option<u32>,
+    ) -> Vec<SimResult> {
+        let mut outcomes = outcomes.collect::<Vec<_>>();
+        // Break ties by result, so the order doesn't depend on how the
+        // outcomes were hashed.
+        outcomes.sort_unstable_by(|(a_result, a), (b_result, b)| {
+            b.partial_cmp(a).unwrap().then(a_result.cmp(b_result))
+        });
+
+        outcomes
+            .into_iter()
+            .map(|(result, probability)| SimResult {
+                counts: result,
+                probability,
+                interval: runs.map(|runs| wilson_interval(probability, runs)),
+                score: self.eval_result(result),
+            })
+            .collect()
+    }
+
+    /// Final score of a stone that ended with `result` successes per row.
+    pub fn eval_result(&self, result: [u8; 3]) -> f64 {
+        self.scoring.eval(result, self.count)
+    }
+}
+
+/// 95% Wilson score interval for a probability estimated as `p` from `runs`
+/// trials. Unlike the usual normal approximation, it stays within 0 to 1 and
+/// isn't zero wide for outcomes that came up never or every time.
+fn wilson_interval(p: f64, runs: u32) -> (f64, f64) {
+    const Z: f64 = 1.96;
+    let n = f64::from(runs);
+    let z2_n = Z * Z / n;
+    let center = (p + z2_n / 2.0) / (1.0 + z2_n);
+    let half_width = Z / (1.0 + z2_n) * (p * (1.0 - p) / n + z2_n / (4.0 * n)).sqrt();
+    (
+        (center - half_width).max(0.0),
+        (center + half_width).min(1.0),
+    )
+}
+
+fn count_successes(gs: &GameState) -> [u8; 3] {
+    [
+        gs.row(0).iter().filter(|&&x| x).count() as u8,
+        gs.row(1).iter().filter(|&&x| x).count() as u8,
+        gs.row(2).iter().filter(|&&x| x).count() as u8,
+    ]
+}
+
+/// All triples `t` with `t[i] <= max[i]`, in lexicographic order.
+fn triples_up_to(max: [u8; 3]) -> impl Iterator<Item = [u8; 3]> {
+    (0..=max[0])
+        .flat_map(move |a| (0..=max[1]).flat_map(move |b| (0..=max[2]).map(move |c| [a, b, c])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Engravings pay off at 5, 7, 9 and 10 nodes; the negative only hurts
+    // from 5 on.
+    const SKILL: [f64; TABLE_LEN] = [
+        0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 2.0, 2.0, 3.0, 4.0, 4.0, 4.0, 4.0, 4.0, 4.0, 4.0,
+    ];
+    const NEGATIVE: [f64; TABLE_LEN] = [
+        0.0, 0.0, 0.0, 0.0, 0.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0,
+        -2.0,
+    ];
+
+    #[test]
+    fn table_tracks_successes_up_to_its_last_breakpoint() {
+        let scoring = Scoring::Table {
+            payoff: [SKILL, SKILL, NEGATIVE],
+        };
+        assert_eq!(scoring.tracking(16), ([10, 10, 5], [0.0, 0.0, 0.0]));
+        assert_eq!(scoring.tracking(6), ([5, 5, 5], [0.0, 0.0, 0.0]));
+
+        // A payoff that keeps growing by the same step needs no tracking
+        // past where the step settles.
+        let mut linear = [0.0; TABLE_LEN];
+        for (n, value) in linear.iter_mut().enumerate() {
+            *value = (n.saturating_sub(3)) as f64;
+        }
+        let scoring = Scoring::Table {
+            payoff: [linear, SKILL, NEGATIVE],
+        };
+        assert_eq!(scoring.tracking(16).0[0], 3);
+        assert_eq!(scoring.tracking(16).1[0], 1.0);
+    }
+
+    #[test]
+    fn weights_that_cancel_out_fail_the_build() {
+        let scoring = Scoring::Weighted {
+            success: [1e308, 1e308, -1e308],
+            fail: [-1e308, -1e308, 1e308],
+        };
+        for risk in [Risk::Neutral, Risk::MeanStdDev { lambda: 1.0 }] {
+            let solution = Solution::build(
+                scoring,
+                risk,
+                ChanceLadder::default(),
+                &SuccessRates::advertised(),
+                6,
+            );
+            assert_eq!(solution.unwrap_err(), BuildError::NotANumber);
+        }
+    }
+
+    #[test]
+    fn sampled_policies_match_their_exact_outcomes() {
+        use rand_chacha::ChaCha8Rng;
+
+        let stone = GameState::new(4, ChanceLadder::default());
+        let solution = Solution::build(
+            Scoring::Table {
+                payoff: [SKILL, SKILL, NEGATIVE],
+            },
+            Risk::Neutral,
+            ChanceLadder::default(),
+            &SuccessRates::advertised(),
+            4,
+        )
+        .unwrap();
+        for policy in [Policy::Optimal, Policy::Random] {
+            let exact = solution
+                .policy_outcome_distribution_with_progress(&stone, policy, |_| true)
+                .unwrap();
+            let tally = solution
+                .simulate_policy_with_progress(
+                    20_000,
+                    &stone,
+                    policy,
+                    &mut ChaCha8Rng::seed_from_u64(1),
+                    |_| true,
+                )
+                .unwrap();
+            for result in solution.outcomes_of(&tally) {
+                assert!((result.probability - exact[&result.counts]).abs() < 0.02);
+            }
+        }
+    }
+
+    #[test]
+    fn choices_match_the_stored_best_click() {
+        for risk in [
+            Risk::Neutral,
+            Risk::MeanStdDev { lambda: 1.0 },
+            Risk::Cvar { alpha: 0.25 },
+        ] {
+            let solution = Solution::build(
+                Scoring::Table {
+                    payoff: [SKILL, SKILL, NEGATIVE],
+                },
+                risk,
+                ChanceLadder::default(),
+                &SuccessRates::advertised(),
+                6,
+            )
+            .unwrap();
+            let needed = Solution::memory_needed(
+                Scoring::Table {
+                    payoff: [SKILL, SKILL, NEGATIVE],
+                },
+                risk,
+                ChanceLadder::default(),
+                6,
+            );
+            assert_eq!(solution.memory_size(), needed);
+            let mut stone = GameState::new(6, ChanceLadder::default());
+            while let Some(choices) = solution.sorted_choices(&stone) {
+                let state = State::new(&stone, solution.caps);
+                assert_eq!(solution.lookup(&state).unwrap().1, choices[0].index);
+                assert_eq!(choices[0].std_dev.is_some(), risk.uses_spread());
+                // Alternate results so every row gets some of each.
+                let success = stone.history().len().is_multiple_of(2);
+                stone.record(choices[0].index, success);
+            }
+        }
+    }
+}